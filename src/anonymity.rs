@@ -0,0 +1,24 @@
+use hmac::{Hmac, Mac};
+use poise::serenity_prelude::{GuildId, UserId};
+use sha2::Sha256;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Generates a new random secret for keying author hashes, encoded as hex.
+pub fn generate_secret() -> String {
+    let bytes: [u8; 32] = rand::random();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Derives the pseudonymous hash identifying a user within a single guild.
+///
+/// The hash is keyed with a secret only the bot knows, so it can't be reversed by hashing
+/// every member id, and it includes the guild id, so the same user can't be correlated
+/// across guilds.
+pub fn author_hash(secret: &str, guild_id: GuildId, user_id: UserId) -> String {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(format!("{}:{}", guild_id, user_id).as_bytes());
+
+    format!("{:x}", mac.finalize().into_bytes())
+}
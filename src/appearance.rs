@@ -0,0 +1,281 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude::{
+    ButtonStyle, Color, CreateButton, CreateEmbed, CreateEmbedAuthor, CreateEmbedFooter,
+    ReactionType,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::utils::{CONFESS_BUTTON_ID, split_text};
+
+// Longest part of a confession shown in one embed. Embed descriptions hold up to 4096
+// characters, leaving room for the spoiler markers of confessions with a content warning.
+pub const PART_MAX_CHARS: usize = 4000;
+
+// Most characters Discord accepts in an embed description
+const EMBED_DESCRIPTION_MAX_CHARS: usize = 4096;
+
+// Longest part that still fits an embed behind a spoiler however many markers are escaped in
+// it: escaping at most doubles the text, and the spoiler adds four characters
+const SPOILER_PART_MAX_CHARS: usize = (EMBED_DESCRIPTION_MAX_CHARS - 4) / 2;
+
+/// Button styles that can be chosen for the confession button (link buttons can't open a modal).
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter,
+)]
+pub enum ButtonColor {
+    #[default]
+    Blurple,
+    Grey,
+    Green,
+    Red,
+}
+
+impl From<ButtonColor> for ButtonStyle {
+    fn from(color: ButtonColor) -> Self {
+        match color {
+            ButtonColor::Blurple => ButtonStyle::Primary,
+            ButtonColor::Grey => ButtonStyle::Secondary,
+            ButtonColor::Green => ButtonStyle::Success,
+            ButtonColor::Red => ButtonStyle::Danger,
+        }
+    }
+}
+
+/// How confessions and the confession button look in a guild.
+/// `title`, `footer` and `thread_name` may contain the `{number}` and `{date}` placeholders.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct Appearance {
+    pub title: String,
+    // Embed colour as `#RRGGBB`
+    pub color: String,
+    pub footer: String,
+    pub thumbnail_url: Option<String>,
+    pub thread_name: String,
+    pub button_message_title: String,
+    pub button_message_description: String,
+    pub button_label: String,
+    pub button_color: ButtonColor,
+    // A unicode emoji or a custom one as `<:name:id>`
+    pub button_emoji: Option<String>,
+}
+
+impl Default for Appearance {
+    fn default() -> Self {
+        Self {
+            title: "Anonymous Confession".to_string(),
+            color: "#FFA500".to_string(),
+            footer: "Confessions".to_string(),
+            thumbnail_url: None,
+            thread_name: "Confession #{number}".to_string(),
+            button_message_title: "Anonymous Confessions".to_string(),
+            button_message_description: "Click the button below to submit an anonymous confession.\nA new thread will be created for each submission.\n\n**Note:** All confessions are anonymous and cannot be traced back to you.".to_string(),
+            button_label: "Submit Anonymous Confession".to_string(),
+            button_color: ButtonColor::Blurple,
+            button_emoji: None,
+        }
+    }
+}
+
+/// Parses a colour written as `#RRGGBB` (the `#` is optional).
+pub fn parse_color(input: &str) -> Option<Color> {
+    let hex = input.trim().trim_start_matches('#');
+
+    if hex.len() != 6 {
+        return None;
+    }
+
+    u32::from_str_radix(hex, 16).ok().map(Color::new)
+}
+
+/// Fills in the `{number}` and `{date}` placeholders.
+fn fill_placeholders(template: &str, number: i64, date: DateTime<Utc>) -> String {
+    template
+        .replace("{number}", &number.to_string())
+        .replace("{date}", &date.format("%Y-%m-%d").to_string())
+}
+
+/// Puts the content warning in front of a title or thread name.
+fn with_content_warning(text: String, content_warning: Option<&str>) -> String {
+    match content_warning {
+        Some(content_warning) => format!("[CW: {}] {}", content_warning, text),
+        None => text,
+    }
+}
+
+/// Hides text behind a spoiler. Spoiler markers inside the text would end the spoiler early,
+/// so they are escaped.
+fn spoiler(text: &str) -> String {
+    format!("||{}||", text.replace("||", "\\|\\|"))
+}
+
+/// Splits a confession into the parts shown in one embed each. Behind a spoiler, the rare part
+/// that no longer fits once its spoiler markers are escaped is split further.
+fn content_parts(content: &str, spoilered: bool) -> Vec<String> {
+    let parts = split_text(content, PART_MAX_CHARS);
+    if !spoilered {
+        return parts;
+    }
+
+    parts
+        .into_iter()
+        .flat_map(|part| {
+            if spoiler(&part).chars().count() <= EMBED_DESCRIPTION_MAX_CHARS {
+                vec![part]
+            } else {
+                split_text(&part, SPOILER_PART_MAX_CHARS)
+            }
+        })
+        .collect()
+}
+
+impl Appearance {
+    pub fn color(&self) -> Color {
+        parse_color(&self.color).unwrap_or(Color::from_rgb(255, 165, 0))
+    }
+
+    pub fn button_emoji(&self) -> Option<ReactionType> {
+        self.button_emoji
+            .as_deref()
+            .and_then(|emoji| ReactionType::try_from(emoji).ok())
+    }
+
+    /// Name of the thread or forum post of a confession (at most 100 characters): the title the
+    /// author chose, or the thread name template. Starts with the content warning if there is one.
+    pub fn thread_name(
+        &self,
+        number: i64,
+        date: DateTime<Utc>,
+        title: Option<&str>,
+        content_warning: Option<&str>,
+    ) -> String {
+        let name = match title {
+            Some(title) => title.to_string(),
+            None => fill_placeholders(&self.thread_name, number, date),
+        };
+
+        with_content_warning(name, content_warning)
+            .chars()
+            .take(100)
+            .collect()
+    }
+
+    /// The embeds a confession is posted as, one per part of at most `PART_MAX_CHARS`
+    /// characters. The first one carries the title (the author's title, or the title template)
+    /// and their signature; with several parts, the footers are numbered "Part 1/3" and so on.
+    /// With a content warning, the warning is shown in the title and every part is hidden
+    /// behind a spoiler.
+    pub fn confession_embeds(
+        &self,
+        number: i64,
+        date: DateTime<Utc>,
+        content: &str,
+        title: Option<&str>,
+        content_warning: Option<&str>,
+        signature: Option<&str>,
+    ) -> Vec<CreateEmbed> {
+        let mut parts = content_parts(content, content_warning.is_some());
+        if parts.is_empty() {
+            parts.push(String::new());
+        }
+
+        let title = match title {
+            Some(title) => title.to_string(),
+            None => fill_placeholders(&self.title, number, date),
+        };
+        let title = with_content_warning(title, content_warning);
+        let footer = fill_placeholders(&self.footer, number, date);
+
+        parts
+            .iter()
+            .enumerate()
+            .map(|(index, part)| {
+                let footer = match (parts.len(), footer.is_empty()) {
+                    (1, _) => footer.clone(),
+                    (count, true) => format!("Part {}/{}", index + 1, count),
+                    (count, false) => format!("{} · Part {}/{}", footer, index + 1, count),
+                };
+                let description = match content_warning {
+                    Some(_) => spoiler(part),
+                    None => part.clone(),
+                };
+
+                let mut embed = CreateEmbed::new()
+                    .description(description)
+                    .color(self.color())
+                    .footer(CreateEmbedFooter::new(footer));
+
+                if index > 0 {
+                    return embed;
+                }
+
+                embed = embed.title(title.chars().take(256).collect::<String>());
+
+                if let Some(signature) = signature {
+                    embed = embed.author(CreateEmbedAuthor::new(format!("Signed: {}", signature)));
+                }
+
+                if let Some(thumbnail_url) = &self.thumbnail_url {
+                    embed = embed.thumbnail(thumbnail_url);
+                }
+
+                embed
+            })
+            .collect()
+    }
+
+    /// The embed of the message holding the confession button.
+    pub fn button_message_embed(&self) -> CreateEmbed {
+        CreateEmbed::new()
+            .title(&self.button_message_title)
+            .description(&self.button_message_description)
+            .color(self.color())
+    }
+
+    /// The button that opens the confession modal.
+    pub fn confess_button(&self) -> CreateButton {
+        let mut button = CreateButton::new(CONFESS_BUTTON_ID)
+            .label(&self.button_label)
+            .style(self.button_color.into());
+
+        if let Some(emoji) = self.button_emoji() {
+            button = button.emoji(emoji);
+        }
+
+        button
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spoiler_escapes_markers() {
+        assert_eq!(spoiler("a || b"), "||a \\|\\| b||");
+    }
+
+    #[test]
+    fn spoilered_parts_fit_an_embed() {
+        let content = "|".repeat(PART_MAX_CHARS);
+
+        let parts = content_parts(&content, true);
+        assert!(parts.len() > 1);
+        assert!(
+            parts
+                .iter()
+                .all(|part| spoiler(part).chars().count() <= EMBED_DESCRIPTION_MAX_CHARS)
+        );
+        assert_eq!(parts.concat(), content);
+    }
+
+    #[test]
+    fn parts_without_markers_keep_their_size() {
+        let content = "word ".repeat(1000);
+        assert_eq!(
+            content_parts(&content, true),
+            content_parts(&content, false)
+        );
+        assert_eq!(content_parts(&"|".repeat(PART_MAX_CHARS), false).len(), 1);
+    }
+}
@@ -0,0 +1,127 @@
+use std::io::Cursor;
+
+use image::{
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits, codecs::jpeg::JpegEncoder,
+};
+use poise::serenity_prelude::{Attachment, CreateAttachment};
+
+// Largest width or height accepted, so tiny files can't decode into huge images
+const MAX_DIMENSION: u32 = 8192;
+
+// Quality of re-encoded JPEG images
+const JPEG_QUALITY: u8 = 90;
+
+/// An image attached to a confession, re-encoded by the bot so that no metadata of the
+/// original upload (EXIF, camera, location) is posted.
+#[derive(Clone)]
+pub struct ConfessionImage {
+    pub filename: String,
+    pub data: Vec<u8>,
+}
+
+// Keep the image bytes out of logs
+impl std::fmt::Debug for ConfessionImage {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ConfessionImage")
+            .field("filename", &self.filename)
+            .field("size", &self.data.len())
+            .finish()
+    }
+}
+
+impl ConfessionImage {
+    /// The image as a file to upload with a message. Spoilered files are blurred until clicked,
+    /// but can't be shown inside an embed.
+    pub fn upload(&self, spoiler: bool) -> CreateAttachment {
+        let filename = if spoiler {
+            format!("SPOILER_{}", self.filename)
+        } else {
+            self.filename.clone()
+        };
+
+        CreateAttachment::bytes(self.data.clone(), filename)
+    }
+
+    /// URL referencing the uploaded file from an embed of the same message.
+    pub fn embed_url(&self) -> String {
+        format!("attachment://{}", self.filename)
+    }
+}
+
+/// Checks that an attachment is an image within the size limit, before anything is downloaded.
+/// On failure, the error is a message that can be shown to the user.
+pub fn check_attachment(attachment: &Attachment, max_size_mb: u32) -> Result<(), String> {
+    let is_image = attachment
+        .content_type
+        .as_deref()
+        .is_some_and(|content_type| content_type.starts_with("image/"));
+
+    if !is_image {
+        return Err("Only images can be attached to confessions.".to_string());
+    }
+
+    if u64::from(attachment.size) > u64::from(max_size_mb) * 1024 * 1024 {
+        return Err(format!(
+            "The image is too large. This server accepts images of up to {} MB.",
+            max_size_mb
+        ));
+    }
+
+    Ok(())
+}
+
+/// Downloads an attached image and re-encodes it without its metadata.
+/// On failure, the error is a message that can be shown to the user.
+pub async fn fetch_image(attachment: &Attachment) -> Result<ConfessionImage, String> {
+    let data = attachment.download().await.map_err(|e| {
+        log::error!("Failed to download attachment {}: {:?}", attachment.id, e);
+        "The image could not be downloaded. Please try again.".to_string()
+    })?;
+
+    tokio::task::spawn_blocking(move || strip_metadata(&data))
+        .await
+        .map_err(|e| {
+            log::error!("Image re-encoding task failed: {:?}", e);
+            "The image could not be processed. Please try again.".to_string()
+        })?
+        .map_err(|e| {
+            log::info!("Rejected an unreadable image: {}", e);
+            "The image could not be read. Please attach a PNG, JPEG, GIF or WebP image.".to_string()
+        })
+}
+
+/// Decodes the image and encodes its pixels again. The encoders only write the pixel data,
+/// so EXIF and other metadata are dropped; the EXIF orientation is applied beforehand so the
+/// image doesn't end up sideways.
+fn strip_metadata(data: &[u8]) -> image::ImageResult<ConfessionImage> {
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    reader.limits(limits);
+
+    let format = reader.format();
+    let mut decoder = reader.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+
+    let mut output = Cursor::new(Vec::new());
+
+    // Photos stay JPEG to keep them small, everything else becomes a PNG
+    let filename = if format == Some(ImageFormat::Jpeg) {
+        image
+            .to_rgb8()
+            .write_with_encoder(JpegEncoder::new_with_quality(&mut output, JPEG_QUALITY))?;
+        "confession.jpg"
+    } else {
+        image.write_to(&mut output, ImageFormat::Png)?;
+        "confession.png"
+    };
+
+    Ok(ConfessionImage {
+        filename: filename.to_string(),
+        data: output.into_inner(),
+    })
+}
@@ -0,0 +1,364 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+};
+
+use chrono::{DateTime, Utc};
+use crypto_box::{PublicKey, SecretKey, aead::OsRng};
+use poise::serenity_prelude::{ChannelId, GuildId};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+use crate::logging::AUDIT_LOG_NAME;
+
+// Marks an encrypted audit record: `enc:<key id>:<hex ciphertext>`
+const ENCRYPTED_PREFIX: &str = "enc:";
+
+/// Why a record could not be decrypted.
+enum Skipped {
+    OtherKey(String),
+    Unreadable,
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// Short identifier of a key pair, so that records can be matched with the private key that
+/// opens them after the key has been rotated.
+fn key_id(public_key: &PublicKey) -> String {
+    to_hex(&Sha256::digest(public_key.as_bytes())[..8])
+}
+
+/// The public key audit records are encrypted with. Only the matching private key, which is
+/// kept off the bot's host, can read them again.
+#[derive(Clone)]
+pub struct AuditKey {
+    public_key: PublicKey,
+    id: String,
+}
+
+impl AuditKey {
+    /// Parses a hex-encoded public key as written by `generate-audit-key`.
+    pub fn from_hex(hex: &str) -> Result<Self, String> {
+        let public_key = from_hex(hex.trim())
+            .and_then(|bytes| PublicKey::from_slice(&bytes).ok())
+            .ok_or("the audit public key must be 64 hexadecimal characters")?;
+
+        Ok(Self {
+            id: key_id(&public_key),
+            public_key,
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Encrypts an audit record into a sealed box stamped with the key id. Sealed boxes use a
+    /// throwaway sender key, so not even the bot can decrypt what it wrote.
+    pub fn seal(&self, record: &str) -> String {
+        let ciphertext = self
+            .public_key
+            .seal(&mut OsRng, record.as_bytes())
+            .expect("Sealing an audit record can't fail");
+
+        format!("{}{}:{}", ENCRYPTED_PREFIX, self.id, to_hex(&ciphertext))
+    }
+}
+
+/// An audit log entry as read back from the log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    // `confession` or `reply`
+    pub kind: String,
+    // One of the `AuditOutcome`s, in snake case
+    pub outcome: String,
+    pub guild_id: GuildId,
+    pub channel_id: Option<ChannelId>,
+    pub thread_id: Option<ChannelId>,
+    pub confession_number: Option<i64>,
+    // Missing from encrypted and redacted entries
+    #[serde(default)]
+    pub author_hash: Option<String>,
+    #[serde(default)]
+    pub content: Option<String>,
+    // The encrypted author hash and content, which only `decrypt-audit` can open
+    #[serde(default)]
+    pub sealed: Option<String>,
+    #[serde(default)]
+    pub redacted: bool,
+}
+
+/// Filters for searching a guild's audit entries.
+#[derive(Debug, Clone)]
+pub struct AuditQuery {
+    pub guild_id: GuildId,
+    // Matches author hashes starting with this, so shortened hashes can be searched for
+    pub author_hash: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub confession_number: Option<i64>,
+    // Matches entries whose content contains this, ignoring case
+    pub text: Option<String>,
+}
+
+impl AuditQuery {
+    /// Returns whether the entry matches every filter. Encrypted and redacted entries have no
+    /// author hash or content to match, so they are left out when searching by either.
+    fn matches(&self, record: &AuditRecord) -> bool {
+        record.guild_id == self.guild_id
+            && self.from.is_none_or(|from| record.timestamp >= from)
+            && self.until.is_none_or(|until| record.timestamp < until)
+            && self
+                .confession_number
+                .is_none_or(|number| record.confession_number == Some(number))
+            && self.author_hash.as_deref().is_none_or(|hash| {
+                record
+                    .author_hash
+                    .as_deref()
+                    .is_some_and(|author_hash| author_hash.starts_with(&hash.to_lowercase()))
+            })
+            && self.text.as_deref().is_none_or(|text| {
+                record
+                    .content
+                    .as_deref()
+                    .is_some_and(|content| content.to_lowercase().contains(&text.to_lowercase()))
+            })
+    }
+}
+
+/// Reads the entries of the audit log in `directory` and its rotated files that match the
+/// query, newest first. Lines from before the audit log switched to JSON are skipped.
+pub fn search(directory: &Path, query: &AuditQuery) -> std::io::Result<Vec<AuditRecord>> {
+    let files = match audit_log_files(directory) {
+        Ok(files) => files,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut records = Vec::new();
+    for path in files {
+        for line in BufReader::new(File::open(path)?).lines() {
+            if let Ok(record) = serde_json::from_str::<AuditRecord>(&line?)
+                && query.matches(&record)
+            {
+                records.push(record);
+            }
+        }
+    }
+
+    records.reverse();
+    Ok(records)
+}
+
+/// Runs a command-line subcommand if one was given. Returns the exit code, or `None` to start
+/// the bot. `log_directory` holds the audit log.
+pub fn run_cli(args: &[String], log_directory: &Path) -> Option<i32> {
+    let (command, args) = args.split_first()?;
+
+    let result = match command.as_str() {
+        "generate-audit-key" => generate_key(args),
+        "decrypt-audit" => decrypt(args, log_directory),
+        _ => Err(format!(
+            "Unknown command `{}`. Available commands: generate-audit-key, decrypt-audit",
+            command
+        )),
+    };
+
+    Some(match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            1
+        }
+    })
+}
+
+/// `generate-audit-key <private key file>`: creates a key pair, writes the private key to a new
+/// file and prints the public key to put into `config.json`.
+fn generate_key(args: &[String]) -> Result<(), String> {
+    let [path] = args else {
+        return Err("Usage: generate-audit-key <private key file>".to_string());
+    };
+
+    let secret_key = SecretKey::generate(&mut OsRng);
+    let public_key = secret_key.public_key();
+
+    let mut options = OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    options
+        .open(path)
+        .and_then(|mut file| writeln!(file, "{}", to_hex(&secret_key.to_bytes())))
+        .map_err(|e| format!("Could not write the private key to {}: {}", path, e))?;
+
+    println!(
+        "Private key written to {}. Keep it off the bot's host.",
+        path
+    );
+    println!("Key id: {}", key_id(&public_key));
+    println!(
+        "Add this to config.json to encrypt new audit records:\n\"audit_public_key\": \"{}\"",
+        to_hex(public_key.as_bytes())
+    );
+    Ok(())
+}
+
+/// `decrypt-audit <private key file> [--search <text>] [log files...]`: prints the audit log
+/// with the records of this key decrypted, optionally only the lines containing `text`. Reads
+/// the current and rotated audit logs when no files are given.
+fn decrypt(args: &[String], log_directory: &Path) -> Result<(), String> {
+    let usage = "Usage: decrypt-audit <private key file> [--search <text>] [log files...]";
+    let (key_path, mut args) = args.split_first().ok_or(usage)?;
+
+    let secret_key = fs::read_to_string(key_path)
+        .ok()
+        .and_then(|hex| from_hex(hex.trim()))
+        .and_then(|bytes| SecretKey::from_slice(&bytes).ok())
+        .ok_or_else(|| format!("{} does not contain a private audit key", key_path))?;
+    let own_id = key_id(&secret_key.public_key());
+
+    let mut search = None;
+    let mut files = Vec::new();
+    while let Some((arg, rest)) = args.split_first() {
+        if arg == "--search" {
+            let (text, rest) = rest.split_first().ok_or(usage)?;
+            search = Some(text.to_lowercase());
+            args = rest;
+        } else {
+            files.push(PathBuf::from(arg));
+            args = rest;
+        }
+    }
+
+    if files.is_empty() {
+        files = audit_log_files(log_directory)
+            .map_err(|e| format!("Could not list {}: {}", log_directory.display(), e))?;
+    }
+
+    let mut other_keys = Vec::new();
+    let mut unreadable = 0;
+
+    for path in &files {
+        let file =
+            File::open(path).map_err(|e| format!("Could not open {}: {}", path.display(), e))?;
+
+        for line in BufReader::new(file).lines() {
+            let line = line.map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+
+            let line = match decrypt_line(&secret_key, &own_id, line) {
+                Ok(line) => line,
+                Err(Skipped::OtherKey(id)) => {
+                    if !other_keys.contains(&id) {
+                        other_keys.push(id);
+                    }
+                    continue;
+                }
+                Err(Skipped::Unreadable) => {
+                    unreadable += 1;
+                    continue;
+                }
+            };
+
+            if search
+                .as_ref()
+                .is_none_or(|search| line.to_lowercase().contains(search))
+            {
+                println!("{}", line);
+            }
+        }
+    }
+
+    if !other_keys.is_empty() {
+        eprintln!(
+            "Skipped records encrypted with other keys: {}",
+            other_keys.join(", ")
+        );
+    }
+
+    if unreadable > 0 {
+        eprintln!("Skipped {} records that could not be decrypted", unreadable);
+    }
+
+    Ok(())
+}
+
+/// Decrypts an audit log line: the `sealed` field of a JSON entry, or the message of a line
+/// written before the audit log switched to JSON. Lines without a sealed record are returned
+/// as they are.
+fn decrypt_line(secret_key: &SecretKey, own_id: &str, line: String) -> Result<String, Skipped> {
+    if let Ok(Value::Object(mut entry)) = serde_json::from_str(&line) {
+        let Some(Value::String(sealed)) = entry.remove("sealed") else {
+            return Ok(line);
+        };
+
+        let Ok(Value::Object(contents)) = serde_json::from_str(&open(secret_key, own_id, &sealed)?)
+        else {
+            return Err(Skipped::Unreadable);
+        };
+        entry.extend(contents);
+        return Ok(Value::Object(entry).to_string());
+    }
+
+    match line.rsplit_once(" | ") {
+        Some((head, message)) if message.starts_with(ENCRYPTED_PREFIX) => {
+            Ok(format!("{} | {}", head, open(secret_key, own_id, message)?))
+        }
+        _ => Ok(line),
+    }
+}
+
+/// Opens a sealed record, if it was encrypted with this key.
+fn open(secret_key: &SecretKey, own_id: &str, sealed: &str) -> Result<String, Skipped> {
+    let (id, ciphertext) = sealed
+        .strip_prefix(ENCRYPTED_PREFIX)
+        .and_then(|sealed| sealed.split_once(':'))
+        .ok_or(Skipped::Unreadable)?;
+
+    if id != own_id {
+        return Err(Skipped::OtherKey(id.to_string()));
+    }
+
+    from_hex(ciphertext)
+        .and_then(|ciphertext| secret_key.unseal(&ciphertext).ok())
+        .and_then(|plaintext| String::from_utf8(plaintext).ok())
+        .ok_or(Skipped::Unreadable)
+}
+
+/// The audit log and its rotated files, oldest first.
+pub fn audit_log_files(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut files: Vec<(u32, PathBuf)> = fs::read_dir(directory)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?.strip_prefix(AUDIT_LOG_NAME)?;
+
+            // `confessions_audit.log` is the current file, `confessions_audit.<n>.log` the
+            // rotated ones with higher numbers being older
+            let age = match name {
+                ".log" => 0,
+                _ => name.strip_prefix('.')?.strip_suffix(".log")?.parse().ok()?,
+            };
+            Some((age, path))
+        })
+        .collect();
+
+    files.sort_by_key(|(age, _)| std::cmp::Reverse(*age));
+    Ok(files.into_iter().map(|(_, path)| path).collect())
+}
@@ -0,0 +1,137 @@
+use poise::serenity_prelude::{
+    AutocompleteChoice, ComponentInteraction, ComponentInteractionDataKind, CreateActionRow,
+    CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, GuildId, ResolvedValue,
+};
+
+use crate::{Context, Data, config::Config, utils::BOARD_SELECT_ID};
+
+// Value of the select menu option for the guild's default board
+const DEFAULT_BOARD: &str = "default";
+
+/// Normalises a board name as typed by a user. Board names are case-insensitive.
+pub fn board_name(input: &str) -> String {
+    input.trim().to_lowercase()
+}
+
+/// Checks that a name can be used for a new board.
+pub fn validate_board_name(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > 32 {
+        return Err("Error: Board names must be 1 to 32 characters long.".to_string());
+    }
+
+    if !name
+        .chars()
+        .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return Err(
+            "Error: Board names may only contain letters, digits, `-` and `_`.".to_string(),
+        );
+    }
+
+    if name == DEFAULT_BOARD {
+        return Err(format!(
+            "Error: `{}` is reserved for the board set with `/set_confession_thread`.",
+            DEFAULT_BOARD
+        ));
+    }
+
+    Ok(())
+}
+
+/// Resolves the board a confession is submitted to. `None` stands for the default board.
+/// On failure, the error is a message that can be shown to the user.
+pub async fn resolve_board(
+    data: &Data,
+    guild_id: GuildId,
+    board: Option<&str>,
+) -> Result<Option<String>, String> {
+    let board = board.map(board_name);
+    let config = data.config.read().await;
+
+    if config.board(guild_id, board.as_deref()).is_some() {
+        return Ok(board);
+    }
+
+    match board {
+        Some(name) => Err(format!("This server has no confession board called `{}`.", name)),
+        None if !config.guild(guild_id).boards.is_empty() => {
+            Err("Please choose the board to post your confession on.".to_string())
+        }
+        None => Err("The confession channel has not been set up for this guild. Please ask a staff member to use `/set_confession_thread`.".to_string()),
+    }
+}
+
+/// Offers the named boards of the guild.
+pub async fn autocomplete_board(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Vec::new();
+    };
+
+    let partial = board_name(partial);
+
+    ctx.data()
+        .config
+        .read()
+        .await
+        .guild(guild_id)
+        .boards
+        .into_keys()
+        .filter(|name| name.contains(&partial))
+        .take(25)
+        .map(|name| AutocompleteChoice::new(name.clone(), name))
+        .collect()
+}
+
+/// Returns the board option already filled in by the user, for use in autocomplete callbacks
+/// of other options of the same command.
+pub fn board_argument(ctx: Context<'_>) -> Option<String> {
+    let poise::Context::Application(ctx) = ctx else {
+        return None;
+    };
+
+    ctx.args.iter().find_map(|option| match &option.value {
+        ResolvedValue::String(value) if option.name == "board" => Some(board_name(value)),
+        _ => None,
+    })
+}
+
+/// The select menu of the `/confessembed` message letting authors choose a board.
+/// `None` if the guild has no named boards.
+pub fn board_menu(config: &Config, guild_id: GuildId) -> Option<CreateActionRow> {
+    let boards = config.guild(guild_id).boards;
+
+    if boards.is_empty() {
+        return None;
+    }
+
+    let default_board = config
+        .confession_threads
+        .contains_key(&guild_id)
+        .then(|| CreateSelectMenuOption::new("Default board", DEFAULT_BOARD));
+
+    let options = default_board
+        .into_iter()
+        .chain(
+            boards
+                .into_keys()
+                .take(24)
+                .map(|name| CreateSelectMenuOption::new(name.clone(), name)),
+        )
+        .collect();
+
+    Some(CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(BOARD_SELECT_ID, CreateSelectMenuKind::String { options })
+            .placeholder("Choose a board to confess on"),
+    ))
+}
+
+/// Returns the board picked in the `/confessembed` select menu (`None` for the default board).
+pub fn selected_board(component: &ComponentInteraction) -> Option<String> {
+    match &component.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values
+            .first()
+            .filter(|value| *value != DEFAULT_BOARD)
+            .cloned(),
+        _ => None,
+    }
+}
@@ -0,0 +1,112 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::{
+    self as serenity, ChannelFlags, ComponentInteraction, ComponentInteractionDataKind,
+    CreateActionRow, CreateSelectMenu, CreateSelectMenuKind, CreateSelectMenuOption, ForumTag,
+    ForumTagId, GuildChannel, GuildId,
+};
+
+use crate::{Data, config::ForumTags};
+
+// Value of the select menu option for confessions without a category
+const NO_CATEGORY: &str = "none";
+
+/// Returns the categories authors can choose from: the configured tags the board's Forum
+/// channel still offers. Empty if there are none or the channel isn't a Forum.
+pub async fn available_categories(
+    data: &Data,
+    cache: &serenity::Http,
+    guild_id: GuildId,
+    board: Option<&str>,
+) -> Vec<ForumTag> {
+    let Some(board) = data.config.read().await.board(guild_id, board) else {
+        return Vec::new();
+    };
+
+    let target_channel_id = board.channel;
+    let categories = board.settings.forum_tags.categories;
+
+    if categories.is_empty() {
+        return Vec::new();
+    }
+
+    match target_channel_id.to_channel(cache).await {
+        Ok(serenity::Channel::Guild(channel)) if channel.kind == serenity::ChannelType::Forum => {
+            channel
+                .available_tags
+                .into_iter()
+                .filter(|tag| categories.contains(&tag.id))
+                .collect()
+        }
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            log::error!("Failed to fetch channel {}: {:?}", target_channel_id, e);
+            Vec::new()
+        }
+    }
+}
+
+/// Drops the configured tags a newly chosen confession channel doesn't have.
+/// Returns notes for the administrator, empty if there is nothing to tell.
+pub fn reconcile_forum_tags(channel: &GuildChannel, forum_tags: &mut ForumTags) -> String {
+    let mut notes = String::new();
+
+    if channel.kind != serenity::ChannelType::Forum {
+        return notes;
+    }
+
+    let available: Vec<_> = channel.available_tags.iter().map(|tag| tag.id).collect();
+    let removed = forum_tags.retain_available(&available);
+
+    if !removed.is_empty() {
+        notes.push_str(&format!(
+            "\nRemoved {} configured forum tag(s) this forum doesn't have. Use `/confession tags` to pick new ones.",
+            removed.len()
+        ));
+    }
+
+    if channel.flags.contains(ChannelFlags::REQUIRE_TAG) && forum_tags.default_tag.is_none() {
+        notes.push_str("\nThis forum requires a tag on every post. Set a default tag with `/confession tags`, otherwise confessions can't be posted.");
+    }
+
+    notes
+}
+
+/// The select menu asking the author for the category of their confession.
+pub fn category_menu(custom_id: &str, categories: &[ForumTag]) -> CreateActionRow {
+    let options = std::iter::once(CreateSelectMenuOption::new("No category", NO_CATEGORY))
+        .chain(
+            categories
+                .iter()
+                .take(24)
+                .map(|tag| CreateSelectMenuOption::new(&tag.name, tag.id.to_string())),
+        )
+        .collect();
+
+    CreateActionRow::SelectMenu(
+        CreateSelectMenu::new(custom_id, CreateSelectMenuKind::String { options })
+            .placeholder("Choose a category for your confession"),
+    )
+}
+
+/// Waits for the author to pick a category from the menu sent with `custom_id`.
+/// Returns the select interaction, which can still be answered with the confession modal.
+pub async fn await_category(
+    ctx: &serenity::Context,
+    custom_id: String,
+) -> Option<(ComponentInteraction, Option<ForumTagId>)> {
+    let interaction = serenity::collector::ComponentInteractionCollector::new(&ctx.shard)
+        .filter(move |interaction| interaction.data.custom_id == custom_id)
+        .timeout(Duration::from_secs(600))
+        .await?;
+
+    let forum_tag = match &interaction.data.kind {
+        ComponentInteractionDataKind::StringSelect { values } => values
+            .first()
+            .and_then(|value| value.parse::<u64>().ok())
+            .map(ForumTagId::new),
+        _ => None,
+    };
+
+    Some((interaction, forum_tag))
+}
@@ -1,18 +1,56 @@
-use chrono::{DateTime, Utc};
-use sha2::{Digest, Sha256};
-use std::sync::Arc;
+use chrono::{DateTime, Duration, Utc};
 
-use crate::{Data, Error, config::Config, logging::log_confession, utils::ConfessionModal};
+use crate::{
+    Data, Error,
+    attachment::{check_attachment, fetch_image},
+    commands::{
+        board::{autocomplete_board, resolve_board},
+        category::{available_categories, await_category, category_menu},
+        confession::ban::ban_notice,
+        preview::{PreviewChoice, await_preview_choice, confession_preview},
+        reply::reply_button,
+        review::submit_for_review,
+    },
+    config::{PostMode, ThreadSettings},
+    filter::{self, Verdict},
+    logging::{AuditEntry, AuditOutcome},
+    rate_limit,
+    sanitize::{no_mentions, sanitize_mentions},
+    schedule,
+    storage::Draft,
+    utils::{CATEGORY_SELECT_PREFIX, ConfessionModal},
+};
 use poise::{
-    ApplicationContext, CreateReply, Modal,
+    ApplicationContext, CreateReply,
     serenity_prelude::{
-        self as serenity, AutoArchiveDuration, CacheHttp, Context, CreateEmbed, CreateEmbedFooter,
-        CreateForumPost, CreateInteractionResponse, CreateInteractionResponseMessage,
-        CreateMessage, CreateThread, GuildId, Mentionable, ModalInteraction,
+        self as serenity, Attachment, Builder, CacheHttp, ChannelId, ComponentInteraction, Context,
+        CreateActionRow, CreateForumPost, CreateInteractionResponse,
+        CreateInteractionResponseMessage, CreateMessage, CreateThread, EditInteractionResponse,
+        EditThread, ForumTagId, GuildId, InteractionId, Mentionable, MessageId, ModalInteraction,
+        UserId,
     },
 };
-use serenity::{ChannelType, Color};
-use tokio::sync::RwLock;
+use serenity::ChannelType;
+
+// Shown in place of a confession preview the author stopped responding to
+const PREVIEW_EXPIRED: &str = "This preview has expired. Please submit your confession again.";
+
+// Reply to authors attaching an image in a server that doesn't accept them
+const IMAGES_DISABLED: &str = "This server does not accept images with confessions.";
+
+/// What happened to a confession that was let through: posted now, or queued until later.
+pub enum Posting {
+    Published(PublishedConfession),
+    Scheduled(DateTime<Utc>),
+}
+
+/// Where a published confession ended up.
+pub struct PublishedConfession {
+    pub channel_id: ChannelId,
+    // `None` when the guild posts confessions without a thread
+    pub thread_id: Option<ChannelId>,
+    pub number: i64,
+}
 
 /// Submit an anonymous confession.
 #[poise::command(
@@ -23,73 +61,537 @@ use tokio::sync::RwLock;
         "Submit an anonymous confession (all submissions are anonymous)."
     )
 )]
-pub async fn confess(ctx: ApplicationContext<'_, Data, Error>) -> Result<(), Error> {
-    // 1. Execute the modal and wait for submission
-    let data = match ConfessionModal::execute(ctx).await {
-        Ok(Some(data)) => data,
-        Ok(None) => {
-            // User cancelled the modal
+pub async fn confess(
+    ctx: ApplicationContext<'_, Data, Error>,
+    #[description = "Board to post your confession on (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+    #[description = "An image to post with your confession, if this server allows it."]
+    image: Option<Attachment>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .expect("Guild ID should be present in guild-only command");
+
+    // 1. Turn banned or rate limited users away before they spend time writing a confession
+    if let Some(notice) = submission_blocked(ctx.data, guild_id, ctx.author().id).await? {
+        ctx.send(CreateReply::default().content(notice).ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+
+    let board = match resolve_board(ctx.data, guild_id, board.as_deref()).await {
+        Ok(board) => board,
+        Err(notice) => {
+            ctx.send(CreateReply::default().content(notice).ephemeral(true))
+                .await?;
             return Ok(());
         }
-        Err(e) => {
-            log::error!("Error executing modal: {:?}", e);
-            ctx.say("An error occurred while processing the confession modal.")
+    };
+
+    // Check the image before the author writes their confession. It is only downloaded once
+    // the confession has been submitted.
+    if let Some(image) = &image {
+        let settings = ctx.data.config.read().await.guild(guild_id).attachments;
+        let checked = if settings.enabled {
+            check_attachment(image, settings.max_size_mb)
+        } else {
+            Err(IMAGES_DISABLED.to_string())
+        };
+
+        if let Err(notice) = checked {
+            ctx.send(CreateReply::default().content(notice).ephemeral(true))
                 .await?;
-            return Err(Box::new(e));
+            return Ok(());
         }
-    };
-    let confession_content = data.content.trim().to_string();
+    }
 
-    // 2. Send the confession using the shared logic
-    let reply = send_confession_logic(
-        ctx.guild_id()
-            .expect("Guild ID should be present in guild-only command"),
-        &ctx.author(),
-        ctx.data.config.clone(),
-        &ctx.http(),
-        confession_content,
+    // 2. Let the author pick a category first if the board offers some
+    let categories = available_categories(ctx.data, ctx.http(), guild_id, board.as_deref()).await;
+
+    if !categories.is_empty() {
+        let custom_id = format!("{}:{}", CATEGORY_SELECT_PREFIX, ctx.id());
+        let handle = ctx
+            .send(
+                CreateReply::default()
+                    .content("Choose a category for your confession:")
+                    .components(vec![category_menu(&custom_id, &categories)])
+                    .ephemeral(true),
+            )
+            .await?;
+
+        let Some((select, forum_tag)) = await_category(ctx.serenity_context(), custom_id).await
+        else {
+            return Ok(());
+        };
+
+        handle
+            .edit(
+                poise::Context::Application(ctx),
+                CreateReply::default()
+                    .allowed_mentions(no_mentions())
+                    .content("Category chosen. Write your confession in the form that opened.")
+                    .components(vec![]),
+            )
+            .await?;
+
+        let draft = Draft {
+            forum_tag,
+            board,
+            ..Default::default()
+        };
+        return open_confession_modal(
+            ctx.serenity_context(),
+            ctx.data,
+            guild_id,
+            (select.id, &select.token),
+            draft,
+            image,
+        )
+        .await;
+    }
+
+    // 3. Open the modal; the submission is sent and answered once the author submits it
+    let draft = Draft {
+        board,
+        ..Default::default()
+    };
+    open_confession_modal(
+        ctx.serenity_context(),
+        ctx.data,
+        guild_id,
+        (ctx.interaction.id, &ctx.interaction.token),
+        draft,
+        image,
     )
-    .await;
+    .await
+}
+
+/// Handles the confession button and the board select menu of the `/confessembed` message:
+/// asks for a category if the board offers some, then opens the confession modal.
+pub async fn handle_confess_button(
+    ctx: &Context,
+    data: &Data,
+    component: &ComponentInteraction,
+    board: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = component
+        .guild_id
+        .expect("Confession button should only be sent in guilds");
+
+    // Turn banned or rate limited users away before they spend time writing a confession
+    let board = match submission_blocked(data, guild_id, component.user.id).await? {
+        Some(notice) => Err(notice),
+        None => resolve_board(data, guild_id, board.as_deref()).await,
+    };
+
+    let board = match board {
+        Ok(board) => board,
+        Err(notice) => {
+            component
+                .create_response(
+                    ctx.http(),
+                    CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .allowed_mentions(no_mentions())
+                            .content(notice)
+                            .ephemeral(true),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let categories = available_categories(data, ctx.http(), guild_id, board.as_deref()).await;
+
+    if categories.is_empty() {
+        let draft = Draft {
+            board,
+            ..Default::default()
+        };
+        return open_confession_modal(
+            ctx,
+            data,
+            guild_id,
+            (component.id, &component.token),
+            draft,
+            None,
+        )
+        .await;
+    }
+
+    let custom_id = format!("{}:{}", CATEGORY_SELECT_PREFIX, component.id);
+    component
+        .create_response(
+            ctx.http(),
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .allowed_mentions(no_mentions())
+                    .content("Choose a category for your confession:")
+                    .components(vec![category_menu(&custom_id, &categories)])
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
 
-    // 3. Send the reply to the user
-    ctx.send(CreateReply::default().content(reply).ephemeral(true))
+    let Some((select, forum_tag)) = await_category(ctx, custom_id).await else {
+        return Ok(());
+    };
+
+    component
+        .edit_response(
+            ctx.http(),
+            EditInteractionResponse::new()
+                .allowed_mentions(no_mentions())
+                .content("Category chosen. Write your confession in the form that opened.")
+                .components(vec![]),
+        )
         .await?;
+
+    let draft = Draft {
+        forum_tag,
+        board,
+        ..Default::default()
+    };
+    open_confession_modal(ctx, data, guild_id, (select.id, &select.token), draft, None).await
+}
+
+/// Answers an interaction (given by its id and token) with the confession modal, built from
+/// the guild's form settings, and submits what the author writes along with the choices already
+/// made in `draft`.
+async fn open_confession_modal(
+    ctx: &Context,
+    data: &Data,
+    guild_id: GuildId,
+    interaction: (InteractionId, &str),
+    draft: Draft,
+    image: Option<Attachment>,
+) -> Result<(), Error> {
+    let form = data.config.read().await.guild(guild_id).form;
+    let custom_id = interaction.0.to_string();
+    ConfessionModal::create(&form, None, custom_id.clone())
+        .execute(ctx.http(), interaction)
+        .await?;
+
+    let response = serenity::collector::ModalInteractionCollector::new(&ctx.shard)
+        .filter(move |modal_interaction| modal_interaction.data.custom_id == custom_id)
+        .timeout(std::time::Duration::from_secs(3600))
+        .await;
+
+    if let Some(modal_interaction) = response {
+        let modal = ConfessionModal::parse(modal_interaction.data.clone());
+
+        if let Ok(modal) = modal {
+            handle_modal_submission(ctx, data, &modal_interaction, modal, draft, image).await?;
+        }
+    }
+
     Ok(())
 }
 
 // Generic logic function to handle the core logic of sending a confession
-async fn send_confession_logic<'a>(
+async fn send_confession_logic(
     guild_id: GuildId,
-    author: &'a serenity::User,
-    config: Arc<RwLock<Config>>,
-    cache: &'a serenity::Http,
-    confession_content: String,
+    author: &serenity::User,
+    data: &Data,
+    cache: &serenity::Http,
+    mut draft: Draft,
+    interaction_token: &str,
+    image: Option<Attachment>,
 ) -> String {
+    // The modal may have been opened before the user was banned or ran into a limit
+    match submission_blocked(data, guild_id, author.id).await {
+        Ok(Some(notice)) => return notice,
+        Ok(None) => {}
+        Err(e) => {
+            log::error!("Failed to check whether the submission is allowed: {:?}", e);
+            return "An error occurred while submitting your confession. Please try again later."
+                .to_string();
+        }
+    }
+
     // 1. Log the confession for auditing
-    // Use a hash of the author's ID to maintain anonymity
+    // Use a keyed, per-guild hash of the author's ID to maintain anonymity
     // This allows tracking of multiple requests from the same user without revealing their identity
     // in case they abuse the system in any way
-    let hash = format!("{:x}", Sha256::digest(&author.id.to_string()));
-    log_confession(&hash, &confession_content);
+    let (hash, audit_key) = {
+        let config = data.config.read().await;
+        (config.author_hash(guild_id, author.id), config.audit_key())
+    };
+    AuditEntry::confession(guild_id, &hash, &draft.content, AuditOutcome::Received)
+        .log(audit_key.as_ref());
 
-    // 2. Get the target channel ID and type from configuration
-    let target_channel_id = {
-        let config = config.read().await;
+    // 2. Run the confession through the content filter of its board
+    let (review_channel, attachment_settings, board) = {
+        let config = data.config.read().await;
+        let guild_config = config.guild(guild_id);
+        (
+            guild_config.review_channel,
+            guild_config.attachments,
+            config.board(guild_id, draft.board.as_deref()),
+        )
+    };
 
-        match config.confession_threads.get(&guild_id) {
-            Some(id) => *id,
-            None => {
-                return "The confession channel has not been set up for this guild. Please ask a staff member to use `/set_confession_thread`.".to_string();
+    let Some(board) = board else {
+        return "The confession board no longer exists.".to_string();
+    };
+    let filter_config = board.settings.filter;
+
+    let outcome = filter::apply(&filter_config, &draft.content);
+    draft.content = outcome.content;
+    let mut verdict = outcome.verdict;
+
+    // The content warning, title and signature are shown openly, so they have to pass the
+    // filter as well
+    for field in [
+        &mut draft.content_warning,
+        &mut draft.title,
+        &mut draft.signature,
+    ] {
+        if let Some(text) = field.as_deref() {
+            let field_outcome = filter::apply(&filter_config, text);
+            verdict = verdict.strongest(field_outcome.verdict);
+            *field = Some(field_outcome.content).filter(|text| !text.is_empty());
+        }
+    }
+
+    if let Some(reason) = outcome.needs_warning
+        && draft.content_warning.is_none()
+    {
+        AuditEntry::confession(guild_id, &hash, &draft.content, AuditOutcome::Filtered)
+            .log(audit_key.as_ref());
+        return format!(
+            "Your confession was not submitted because it contains {}, which this server only allows with a content warning. Please submit it again with a content warning.",
+            reason
+        );
+    }
+
+    let mut held_reason = match verdict {
+        Verdict::Allow => None,
+        Verdict::Hold(reason) => Some(format!("held by the content filter: {}", reason)),
+        Verdict::Reject(reason) => {
+            AuditEntry::confession(guild_id, &hash, &draft.content, AuditOutcome::Filtered)
+                .log(audit_key.as_ref());
+            return format!(
+                "Your confession was not submitted because it contains {}, which this server does not allow.",
+                reason
+            );
+        }
+    };
+
+    if draft.content.is_empty() {
+        return "Your confession is empty after applying this server's content filter.".to_string();
+    }
+
+    // Re-upload the attached image without its metadata
+    if let Some(image) = image {
+        if !attachment_settings.enabled {
+            return IMAGES_DISABLED.to_string();
+        }
+
+        match fetch_image(&image).await {
+            Ok(image) => draft.image = Some(image),
+            Err(notice) => return notice,
+        }
+
+        if attachment_settings.review && held_reason.is_none() {
+            held_reason = Some("images need approval".to_string());
+        }
+    }
+
+    // 3. Hold the confession for moderator approval if the guild has review mode enabled,
+    // the filter wants a moderator to look at it or it has an image needing approval
+    if review_channel.is_none() && held_reason.is_some() {
+        return "Your confession needs to be approved by a moderator, but this server has no review channel. Please ask a staff member to use `/set_review_channel`.".to_string();
+    }
+
+    let result = if review_channel.is_some() {
+        submit_for_review(
+            cache,
+            data,
+            guild_id,
+            &hash,
+            &draft,
+            interaction_token,
+            held_reason.as_deref(),
+        )
+        .await
+        .map(|()| {
+            AuditEntry::confession(guild_id, &hash, &draft.content, AuditOutcome::HeldForReview)
+                .log(audit_key.as_ref());
+            "Your anonymous confession has been submitted and is awaiting moderator approval."
+                .to_string()
+        })
+    } else {
+        // 4. Otherwise post it straight away, or when the guild's schedule says so
+        post_or_schedule(guild_id, data, cache, &hash, draft, Utc::now())
+            .await
+            .map(|posting| match posting {
+                Posting::Published(published) => format!(
+                    "Your anonymous confession #{} has been submitted! See the new post/thread in {}.",
+                    published.number,
+                    published.channel_id.mention()
+                ),
+                Posting::Scheduled(post_at) => format!(
+                    "Your anonymous confession has been submitted. To keep its timing from pointing to you, it will be posted around <t:{}:t> (<t:{}:R>).",
+                    post_at.timestamp(),
+                    post_at.timestamp()
+                ),
+            })
+    };
+
+    // 5. Count the submission towards the rate limits once it went through
+    match result {
+        Ok(reply) => {
+            if let Err(e) = data.storage.record_submission(guild_id, &hash) {
+                log::error!("Failed to record submission for rate limiting: {:?}", e);
             }
+            reply
+        }
+        Err(reply) => reply,
+    }
+}
+
+/// Returns a message explaining why the user can't submit a confession right now (because
+/// they are banned or ran into a rate limit), or `None` if they can.
+pub async fn submission_blocked(
+    data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<Option<String>, Error> {
+    if let Some(notice) = ban_notice(data, guild_id, user_id).await? {
+        return Ok(Some(notice));
+    }
+
+    let (limits, author_hashes) = {
+        let config = data.config.read().await;
+        (
+            config.guild(guild_id).rate_limits,
+            config.author_hashes(guild_id, user_id),
+        )
+    };
+
+    let now = Utc::now();
+    let user_submissions =
+        data.storage
+            .submission_times(guild_id, Some(&author_hashes), now - Duration::days(1))?;
+    let guild_submissions = if limits.guild_burst_limit > 0 {
+        data.storage.submission_times(
+            guild_id,
+            None,
+            now - Duration::minutes(limits.guild_burst_window_minutes.into()),
+        )?
+    } else {
+        Vec::new()
+    };
+
+    Ok(
+        rate_limit::check(&limits, &user_submissions, &guild_submissions, now)
+            .map(|limited| limited.message(&limits)),
+    )
+}
+
+/// Posts the confession right away, or queues it if the guild delays confessions.
+/// On failure, the error is a message that can be shown to the user.
+pub async fn post_or_schedule(
+    guild_id: GuildId,
+    data: &Data,
+    cache: &serenity::Http,
+    author_hash: &str,
+    draft: Draft,
+    submitted_at: DateTime<Utc>,
+) -> Result<Posting, String> {
+    let (schedule, audit_key) = {
+        let config = data.config.read().await;
+        (config.guild(guild_id).schedule, config.audit_key())
+    };
+
+    let Some(post_at) = schedule::post_time(&schedule, Utc::now()) else {
+        return publish_confession(guild_id, data, cache, author_hash, draft, submitted_at)
+            .await
+            .map(Posting::Published);
+    };
+
+    match data
+        .storage
+        .insert_scheduled(guild_id, author_hash, &draft, post_at, submitted_at)
+    {
+        Ok(_) => {
+            AuditEntry::confession(
+                guild_id,
+                author_hash,
+                &draft.content,
+                AuditOutcome::Scheduled,
+            )
+            .log(audit_key.as_ref());
+            Ok(Posting::Scheduled(post_at))
+        }
+        Err(e) => {
+            log::error!("Failed to queue confession for guild {}: {:?}", guild_id, e);
+            Err(
+                "An error occurred while saving your confession. Please try again later."
+                    .to_string(),
+            )
         }
+    }
+}
+
+/// Numbers the confession and creates its public thread or forum post.
+/// On failure, the error is a message that can be shown to the user.
+pub async fn publish_confession(
+    guild_id: GuildId,
+    data: &Data,
+    cache: &serenity::Http,
+    author_hash: &str,
+    draft: Draft,
+    submitted_at: DateTime<Utc>,
+) -> Result<PublishedConfession, String> {
+    // 1. Get the target channel ID and the settings of the confession's board
+    let (board, audit_key) = {
+        let config = data.config.read().await;
+        (
+            config.board(guild_id, draft.board.as_deref()),
+            config.audit_key(),
+        )
+    };
+
+    let Some(board) = board else {
+        return Err(match draft.board {
+            Some(name) => format!("The confession board `{}` no longer exists.", name),
+            None => "The confession channel has not been set up for this guild. Please ask a staff member to use `/set_confession_thread`.".to_string(),
+        });
     };
 
+    let target_channel_id = board.channel;
+    let mention_mode = board.settings.filter.mention_mode;
+    let appearance = board.settings.appearance;
+    let thread_settings = board.settings.threads;
+    let default_tag = board.settings.forum_tags.default_tag;
+    let content_warning_tag = board.settings.forum_tags.content_warning_tag;
+
+    // Neutralise mentions last, so moderator edits in the review queue are covered as well
+    let confession_content = sanitize_mentions(&draft.content, mention_mode);
+    if confession_content.is_empty() {
+        return Err("The confession is empty once mentions are removed.".to_string());
+    }
+
+    let sanitize_field = |field: &Option<String>| {
+        field
+            .as_deref()
+            .map(|text| sanitize_mentions(text, mention_mode))
+            .filter(|text| !text.is_empty())
+    };
+    let content_warning = sanitize_field(&draft.content_warning);
+    let title = sanitize_field(&draft.title);
+    let signature = sanitize_field(&draft.signature);
+
     // Fetch channel type
     let channel = match target_channel_id.to_channel(cache).await {
         Ok(c) => c,
         Err(e) => {
             log::error!("Failed to fetch channel {}: {:?}", target_channel_id, e);
-            return "An error occurred while fetching the target channel information. Please try again later.".to_string();
+            return Err("An error occurred while fetching the target channel information. Please try again later.".to_string());
         }
     };
 
@@ -100,22 +602,208 @@ async fn send_confession_logic<'a>(
                 "Target channel {} is not a guild channel.",
                 target_channel_id
             );
-            return "The configured confession channel is not a guild channel.".to_string();
+            return Err("The configured confession channel is not a guild channel.".to_string());
+        }
+    };
+
+    // 2. Reserve the guild's next confession number. The lock is held until the confession
+    // has been posted (or discarded), so concurrent submissions can't leave gaps in the numbering.
+    let _numbering = data.storage.lock_numbering(guild_id).await;
+
+    let reserved = match data.storage.reserve_confession(
+        guild_id,
+        author_hash,
+        &confession_content,
+        target_channel_id,
+        draft.board.as_deref(),
+        submitted_at,
+    ) {
+        Ok(reserved) => reserved,
+        Err(e) => {
+            log::error!("Failed to store confession for guild {}: {:?}", guild_id, e);
+            return Err(
+                "An error occurred while saving your confession. Please try again later."
+                    .to_string(),
+            );
         }
     };
 
-    // Prepare common elements
-    let now: DateTime<Utc> = Utc::now();
-    let thread_name = format!("Confession - {}", now.format("%Y-%m-%d %H:%M:%S UTC"));
+    // Prepare common elements from the board's appearance settings
+    let posted_at = Utc::now();
+    let thread_name = appearance.thread_name(
+        reserved.number,
+        posted_at,
+        title.as_deref(),
+        content_warning.as_deref(),
+    );
+    // Long confessions are split into several embeds. The first one starts the thread, the
+    // others follow it as separate messages since a message holds at most 6000 characters
+    // of embeds.
+    let mut embeds = appearance
+        .confession_embeds(
+            reserved.number,
+            posted_at,
+            &confession_content,
+            title.as_deref(),
+            content_warning.as_deref(),
+            signature.as_deref(),
+        )
+        .into_iter();
+    let embed = embeds.next().expect("A confession has at least one embed");
+
+    let mut message = CreateMessage::new().allowed_mentions(no_mentions());
+
+    // Images go into the embed, unless a content warning asks for them to be spoilered
+    match &draft.image {
+        Some(image) if content_warning.is_none() => {
+            message = message
+                .embed(embed.image(image.embed_url()))
+                .add_file(image.upload(false));
+        }
+        Some(image) => message = message.embed(embed).add_file(image.upload(true)),
+        None => message = message.embed(embed),
+    }
+
+    // Anonymous replies need a thread that is open for discussion
+    let has_thread = channel_kind == ChannelType::Forum || thread_settings.mode == PostMode::Thread;
+    if has_thread && !thread_settings.locked {
+        message = message.components(vec![CreateActionRow::Buttons(vec![reply_button(
+            reserved.number,
+        )])]);
+    }
 
-    let embed = CreateEmbed::new()
-        .title("Anonymous Confession")
-        .description(confession_content)
-        .color(Color::from_rgb(255, 165, 0)) // Orange color
-        .footer(CreateEmbedFooter::new("Confessions"));
+    // Forum posts get the board's default tag, the category the author picked and the content
+    // warning tag if the confession has a warning
+    let forum_tags: Vec<ForumTagId> = default_tag
+        .into_iter()
+        .chain(draft.forum_tag)
+        .chain(content_warning_tag.filter(|_| content_warning.is_some()))
+        .fold(Vec::new(), |mut tags, tag| {
+            if !tags.contains(&tag) {
+                tags.push(tag);
+            }
+            tags
+        });
 
     // 3. Create a new thread/post inside the target channel
-    let _new_thread_id = match channel_kind {
+    let (thread_id, message_id) = match create_confession_post(
+        cache,
+        target_channel_id,
+        channel_kind,
+        &thread_settings,
+        &forum_tags,
+        thread_name,
+        message,
+    )
+    .await
+    {
+        Ok(posted) => posted,
+        Err(reply) => {
+            if let Err(e) = data.storage.discard_confession(reserved.id) {
+                log::error!("Failed to discard confession {}: {:?}", reserved.id, e);
+            }
+            return Err(reply);
+        }
+    };
+
+    if let Err(e) = data
+        .storage
+        .mark_confession_posted(reserved.id, thread_id, message_id)
+    {
+        log::error!(
+            "Failed to record message {} for confession {}: {:?}",
+            message_id,
+            reserved.id,
+            e
+        );
+    }
+
+    // 4. Post the remaining parts below the first one
+    let mut part_message_ids = Vec::new();
+    for embed in embeds {
+        let part = CreateMessage::new()
+            .allowed_mentions(no_mentions())
+            .embed(embed);
+
+        match thread_id
+            .unwrap_or(target_channel_id)
+            .send_message(cache, part)
+            .await
+        {
+            Ok(part_message) => part_message_ids.push(part_message.id),
+            Err(e) => {
+                log::error!(
+                    "Failed to post part {} of confession {}: {:?}",
+                    part_message_ids.len() + 2,
+                    reserved.id,
+                    e
+                );
+                break;
+            }
+        }
+    }
+
+    if !part_message_ids.is_empty()
+        && let Err(e) = data
+            .storage
+            .mark_confession_parts(reserved.id, &part_message_ids)
+    {
+        log::error!(
+            "Failed to record the parts of confession {}: {:?}",
+            reserved.id,
+            e
+        );
+    }
+
+    AuditEntry::confession(
+        guild_id,
+        author_hash,
+        &confession_content,
+        AuditOutcome::Published,
+    )
+    .number(reserved.number)
+    .posted_in(target_channel_id, thread_id)
+    .log(audit_key.as_ref());
+
+    Ok(PublishedConfession {
+        channel_id: target_channel_id,
+        thread_id,
+        number: reserved.number,
+    })
+}
+
+/// Creates the thread or forum post holding the confession message, or just posts the message
+/// when the guild doesn't want threads. Returns the ids of the thread (if any) and of the
+/// confession message.
+async fn create_confession_post(
+    cache: &serenity::Http,
+    target_channel_id: ChannelId,
+    channel_kind: ChannelType,
+    settings: &ThreadSettings,
+    forum_tags: &[ForumTagId],
+    thread_name: String,
+    message: CreateMessage,
+) -> Result<(Option<ChannelId>, MessageId), String> {
+    let posted = match channel_kind {
+        ChannelType::Text | ChannelType::PublicThread | ChannelType::PrivateThread
+            if settings.mode == PostMode::EmbedOnly =>
+        {
+            // Post the embed straight into the channel
+            return match target_channel_id.send_message(cache, message).await {
+                Ok(message) => Ok((None, message.id)),
+                Err(e) => {
+                    log::error!(
+                        "Failed to send confession message in channel {}: {:?}",
+                        target_channel_id,
+                        e
+                    );
+                    Err(
+                        "An error occurred while sending your confession. Please try again later."
+                            .to_string(),
+                    )
+                }
+            };
+        }
         ChannelType::Text | ChannelType::PublicThread | ChannelType::PrivateThread => {
             // Create a thread in a Text channel or a sub-thread in an existing thread
             let new_thread = match target_channel_id
@@ -123,7 +811,8 @@ async fn send_confession_logic<'a>(
                     cache,
                     CreateThread::new(thread_name)
                         .kind(ChannelType::PublicThread)
-                        .auto_archive_duration(AutoArchiveDuration::ThreeDays),
+                        .auto_archive_duration(settings.archive_after.into())
+                        .rate_limit_per_user(settings.slowmode_seconds),
                 )
                 .await
             {
@@ -134,24 +823,23 @@ async fn send_confession_logic<'a>(
                         target_channel_id,
                         e
                     );
-                    return "An error occurred while creating a thread for your confession. Please try again later.".to_string();
+                    return Err("An error occurred while creating a thread for your confession. Please try again later.".to_string());
                 }
             };
 
-            // 4. Send the anonymous confession embed to the new thread
-            match new_thread
-                .send_message(cache, CreateMessage::new().embed(embed))
-                .await
-            {
-                Ok(_) => new_thread.id,
+            // Send the anonymous confession embed to the new thread
+            match new_thread.send_message(cache, message).await {
+                Ok(message) => (new_thread.id, message.id),
                 Err(e) => {
                     log::error!(
                         "Failed to send confession message in thread {}: {:?}",
                         new_thread.id,
                         e
                     );
-                    return "An error occurred while sending your confession. Please try again later."
-                        .to_string();
+                    return Err(
+                        "An error occurred while sending your confession. Please try again later."
+                            .to_string(),
+                    );
                 }
             }
         }
@@ -160,19 +848,22 @@ async fn send_confession_logic<'a>(
             match target_channel_id
                 .create_forum_post(
                     cache,
-                    CreateForumPost::new(thread_name, CreateMessage::new().embed(embed))
-                        .auto_archive_duration(AutoArchiveDuration::ThreeDays),
+                    CreateForumPost::new(thread_name, message)
+                        .auto_archive_duration(settings.archive_after.into())
+                        .rate_limit_per_user(settings.slowmode_seconds)
+                        .set_applied_tags(forum_tags.to_vec()),
                 )
                 .await
             {
-                Ok(thread) => thread.id,
+                // The starter message of a forum post shares its id with the post itself
+                Ok(thread) => (thread.id, MessageId::new(thread.id.get())),
                 Err(e) => {
                     log::error!(
                         "Failed to create forum post in channel {}: {:?}",
                         target_channel_id,
                         e
                     );
-                    return "An error occurred while creating a forum post for your confession. Please try again later.".to_string();
+                    return Err("An error occurred while creating a forum post for your confession. Please try again later.".to_string());
                 }
             }
         }
@@ -182,43 +873,87 @@ async fn send_confession_logic<'a>(
                 target_channel_id,
                 channel_kind
             );
-            return "The configured confession channel is not a supported type (Text, Forum, or Thread).".to_string();
+            return Err("The configured confession channel is not a supported type (Text, Forum, or Thread).".to_string());
         }
     };
 
-    // 5. Acknowledge the submission
-    format!(
-        "Your anonymous confession has been submitted! See the new post/thread in {}.",
-        target_channel_id.mention()
-    )
+    // Lock the thread only once the confession is in it
+    if settings.locked {
+        let (thread_id, _) = posted;
+
+        if let Err(e) = thread_id
+            .edit_thread(cache, EditThread::new().locked(true))
+            .await
+        {
+            log::error!("Failed to lock confession thread {}: {:?}", thread_id, e);
+        }
+    }
+
+    Ok((Some(posted.0), posted.1))
 }
 
-/// Handles the modal submission when triggered by the button interaction.
-/// This function is called by the interaction handler in the next step.
-pub async fn handle_modal_submission<'a>(
+/// Handles the submission of the confession modal, from both `/confess` and the confession
+/// button: shows the author a preview if the guild wants one, otherwise submits right away.
+pub async fn handle_modal_submission(
     ctx: &Context,
-    config: Arc<RwLock<Config>>,
+    data: &Data,
     interaction: &ModalInteraction,
-    data: ConfessionModal,
+    modal: ConfessionModal,
+    draft: Draft,
+    image: Option<Attachment>,
 ) -> Result<(), Error> {
-    let confession_content = data.content.trim().to_string();
-    let reply = send_confession_logic(
+    let guild_id = interaction
+        .guild_id
+        .expect("Guild ID should be present in guild-only command");
+    let draft = modal.into_draft(draft);
+
+    if data.config.read().await.guild(guild_id).form.preview {
+        return preview_confession(ctx, data, guild_id, interaction.clone(), draft, image).await;
+    }
+
+    // Downloading and re-encoding an image can take longer than Discord waits for a response
+    let deferred = image.is_some();
+    if deferred {
         interaction
-            .guild_id
-            .expect("Guild ID should be present in guild-only command"),
+            .create_response(
+                &ctx.http,
+                CreateInteractionResponse::Defer(
+                    CreateInteractionResponseMessage::new().ephemeral(true),
+                ),
+            )
+            .await?;
+    }
+
+    let reply = send_confession_logic(
+        guild_id,
         &interaction.user,
-        config,
+        data,
         ctx.http(),
-        confession_content,
+        draft,
+        &interaction.token,
+        image,
     )
     .await;
 
     // Respond to the interaction with the reply
+    if deferred {
+        interaction
+            .edit_response(
+                &ctx.http,
+                EditInteractionResponse::new()
+                    .allowed_mentions(no_mentions())
+                    .content(reply),
+            )
+            .await?;
+        return Ok(());
+    }
+
     interaction
         .create_response(
             &ctx.http,
             CreateInteractionResponse::Message(
                 CreateInteractionResponseMessage::new()
+                    .allowed_mentions(no_mentions())
                     .content(reply)
                     .ephemeral(true),
             ),
@@ -227,3 +962,140 @@ pub async fn handle_modal_submission<'a>(
 
     Ok(())
 }
+
+/// Shows the author an ephemeral preview of their confession and waits for them to submit it,
+/// edit it (which reopens the modal and updates the preview) or cancel it.
+async fn preview_confession(
+    ctx: &Context,
+    data: &Data,
+    guild_id: GuildId,
+    mut modal_interaction: ModalInteraction,
+    mut draft: Draft,
+    image: Option<Attachment>,
+) -> Result<(), Error> {
+    // The first preview is a new message; after an edit, the modal updates the existing one
+    let mut update = false;
+
+    loop {
+        let key = modal_interaction.id.to_string();
+        let message = match confession_preview(data, guild_id, &draft, image.as_ref(), &key).await {
+            Ok(message) => message,
+            Err(notice) => CreateInteractionResponseMessage::new()
+                .allowed_mentions(no_mentions())
+                .content(notice)
+                .embeds(vec![])
+                .components(vec![]),
+        };
+        let response = if update {
+            CreateInteractionResponse::UpdateMessage(message)
+        } else {
+            CreateInteractionResponse::Message(message.ephemeral(true))
+        };
+        modal_interaction
+            .create_response(ctx.http(), response)
+            .await?;
+        update = true;
+
+        let Some((component, choice)) = await_preview_choice(ctx, key).await else {
+            modal_interaction
+                .edit_response(
+                    ctx.http(),
+                    EditInteractionResponse::new()
+                        .allowed_mentions(no_mentions())
+                        .content(PREVIEW_EXPIRED)
+                        .embeds(vec![])
+                        .components(vec![]),
+                )
+                .await?;
+            return Ok(());
+        };
+
+        match choice {
+            PreviewChoice::Submit => {
+                // Submitting can take a while with an image, so the preview is updated afterwards
+                component
+                    .create_response(ctx.http(), CreateInteractionResponse::Acknowledge)
+                    .await?;
+
+                let reply = send_confession_logic(
+                    guild_id,
+                    &component.user,
+                    data,
+                    ctx.http(),
+                    draft,
+                    &component.token,
+                    image,
+                )
+                .await;
+
+                component
+                    .edit_response(
+                        ctx.http(),
+                        EditInteractionResponse::new()
+                            .allowed_mentions(no_mentions())
+                            .content(reply)
+                            .embeds(vec![])
+                            .components(vec![]),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            PreviewChoice::Cancel => {
+                component
+                    .create_response(
+                        ctx.http(),
+                        CreateInteractionResponse::UpdateMessage(
+                            CreateInteractionResponseMessage::new()
+                                .allowed_mentions(no_mentions())
+                                .content("Your confession was not submitted.")
+                                .embeds(vec![])
+                                .components(vec![]),
+                        ),
+                    )
+                    .await?;
+                return Ok(());
+            }
+            PreviewChoice::Edit => {
+                let form = data.config.read().await.guild(guild_id).form;
+                let custom_id = component.id.to_string();
+                component
+                    .create_response(
+                        ctx.http(),
+                        ConfessionModal::create(&form, Some(&draft), custom_id.clone()),
+                    )
+                    .await?;
+
+                let response = serenity::collector::ModalInteractionCollector::new(&ctx.shard)
+                    .filter(move |modal_interaction| modal_interaction.data.custom_id == custom_id)
+                    .timeout(std::time::Duration::from_secs(600))
+                    .await;
+
+                // The author closed the modal without submitting it. The preview's buttons no
+                // longer work, so replace them with a notice if the interaction is still valid.
+                let Some(edited) = response else {
+                    if let Err(e) = modal_interaction
+                        .edit_response(
+                            ctx.http(),
+                            EditInteractionResponse::new()
+                                .allowed_mentions(no_mentions())
+                                .content(PREVIEW_EXPIRED)
+                                .embeds(vec![])
+                                .components(vec![]),
+                        )
+                        .await
+                    {
+                        log::info!("Could not mark the confession preview as expired: {:?}", e);
+                    }
+                    return Ok(());
+                };
+
+                let Ok(modal) = ConfessionModal::parse(edited.data.clone()) else {
+                    return Ok(());
+                };
+
+                draft = modal.into_draft(draft);
+                modal_interaction = edited;
+            }
+        }
+    }
+}
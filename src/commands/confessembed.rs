@@ -1,7 +1,6 @@
-use poise::serenity_prelude::{self as serenity, CreateEmbed, CreateMessage};
-use serenity::{ButtonStyle, Color};
+use poise::serenity_prelude::{self as serenity, CreateMessage};
 
-use crate::{Context, Error, utils::CONFESS_BUTTON_ID};
+use crate::{Context, Error, commands::board::board_menu, sanitize::no_mentions};
 
 /// Creates a message with a button to submit an anonymous confession.
 #[poise::command(
@@ -14,15 +13,31 @@ use crate::{Context, Error, utils::CONFESS_BUTTON_ID};
     )
 )]
 pub async fn confessembed(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+    let (appearance, has_default_board, board_menu) = {
+        let config = ctx.data().config.read().await;
+        (
+            config.guild(guild_id).settings.appearance,
+            config.confession_threads.contains_key(&guild_id),
+            board_menu(&config, guild_id),
+        )
+    };
+
+    // The button posts to the default board, the select menu (if any) to a named one
+    let mut components = Vec::new();
+    if has_default_board || board_menu.is_none() {
+        components.push(serenity::CreateActionRow::Buttons(vec![
+            appearance.confess_button(),
+        ]));
+    }
+    components.extend(board_menu);
+
     let message = CreateMessage::default()
-        .embed(CreateEmbed::new().title("Anonymous Confessions")
-                .description("Click the button below to submit an anonymous confession.\nA new thread will be created for each submission.\n\n**Note:** All confessions are anonymous and cannot be traced back to you.")
-                .color(Color::ORANGE))
-        .components(vec![serenity::CreateActionRow::Buttons(vec![
-            serenity::CreateButton::new(CONFESS_BUTTON_ID)
-                .label("Submit Anonymous Confession")
-                .style(ButtonStyle::Primary),
-        ])]);
+        .allowed_mentions(no_mentions())
+        .embed(appearance.button_message_embed())
+        .components(components);
 
     ctx.channel_id().send_message(ctx.http(), message).await?;
     ctx.reply("Confession embed sent successfully!").await?;
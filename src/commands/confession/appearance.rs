@@ -0,0 +1,205 @@
+use chrono::Utc;
+use poise::{
+    CreateReply,
+    serenity_prelude::{CreateActionRow, ReactionType},
+};
+
+use crate::{
+    Context, Error,
+    appearance::{Appearance, ButtonColor, parse_color},
+    commands::{
+        board::{autocomplete_board, board_name},
+        reply::reply_button,
+    },
+};
+
+// Number and text shown in the preview confession
+const PREVIEW_NUMBER: i64 = 42;
+const PREVIEW_CONTENT: &str = "This is how confessions will look in this server.";
+
+// Value that clears an optional setting
+const CLEAR: &str = "none";
+
+/// Customise how confessions and the confession button look. Shows a preview of the result.
+#[allow(clippy::too_many_arguments)]
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Customise how confessions and the confession button look, with a preview."
+    )
+)]
+pub async fn appearance(
+    ctx: Context<'_>,
+    #[description = "Confession title. {number} and {date} are replaced."]
+    #[max_length = 256]
+    title: Option<String>,
+    #[description = "Embed colour as #RRGGBB."] color: Option<String>,
+    #[description = "Confession footer. {number} and {date} are replaced."]
+    #[max_length = 2048]
+    footer: Option<String>,
+    #[description = "Image URL shown as the thumbnail of confessions (\"none\" to remove)."]
+    thumbnail_url: Option<String>,
+    #[description = "Name of confession threads and posts. {number} and {date} are replaced."]
+    #[max_length = 100]
+    thread_name: Option<String>,
+    #[description = "Title of the message with the confession button."]
+    #[max_length = 256]
+    button_message_title: Option<String>,
+    #[description = "Text of the message with the confession button."]
+    #[max_length = 4000]
+    button_message_description: Option<String>,
+    #[description = "Label of the confession button."]
+    #[max_length = 80]
+    button_label: Option<String>,
+    #[description = "Colour of the confession button."] button_color: Option<ButtonColor>,
+    #[description = "Emoji of the confession button, e.g. 🤫 or <:name:id> (\"none\" to remove)."]
+    button_emoji: Option<String>,
+    #[description = "Go back to the default appearance before applying the other options."]
+    reset: Option<bool>,
+    #[description = "Board to configure (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+    let board = board.as_deref().map(board_name);
+
+    if let Some(color) = &color
+        && parse_color(color).is_none()
+    {
+        ctx.say("Error: Invalid colour. Use the #RRGGBB format, e.g. `#FFA500`.")
+            .await?;
+        return Ok(());
+    }
+
+    if let Some(url) = &thumbnail_url
+        && url != CLEAR
+        && !url.starts_with("https://")
+    {
+        ctx.say("Error: The thumbnail must be an https:// URL.")
+            .await?;
+        return Ok(());
+    }
+
+    if let Some(emoji) = &button_emoji
+        && emoji != CLEAR
+        && ReactionType::try_from(emoji.as_str()).is_err()
+    {
+        ctx.say("Error: Invalid emoji. Use a unicode emoji or a custom one as `<:name:id>`.")
+            .await?;
+        return Ok(());
+    }
+
+    let clearable = |value: String| (value != CLEAR).then_some(value);
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let Some(board_settings) = config
+        .guild_mut(guild_id)
+        .board_settings_mut(board.as_deref())
+    else {
+        ctx.say(format!(
+            "There is no board called `{}`.",
+            board.unwrap_or_default()
+        ))
+        .await?;
+        return Ok(());
+    };
+    let current = &mut board_settings.appearance;
+
+    if reset == Some(true) {
+        *current = Appearance::default();
+    }
+
+    if let Some(title) = title {
+        current.title = title;
+    }
+
+    if let Some(color) = color.as_deref().and_then(parse_color) {
+        current.color = format!("#{}", color.hex());
+    }
+
+    if let Some(footer) = footer {
+        current.footer = footer;
+    }
+
+    if let Some(thumbnail_url) = thumbnail_url {
+        current.thumbnail_url = clearable(thumbnail_url);
+    }
+
+    if let Some(thread_name) = thread_name {
+        current.thread_name = thread_name;
+    }
+
+    if let Some(button_message_title) = button_message_title {
+        current.button_message_title = button_message_title;
+    }
+
+    if let Some(button_message_description) = button_message_description {
+        // Slash command options can't contain line breaks, so allow writing them as \n
+        current.button_message_description = button_message_description.replace("\\n", "\n");
+    }
+
+    if let Some(button_label) = button_label {
+        current.button_label = button_label;
+    }
+
+    if let Some(button_color) = button_color {
+        current.button_color = button_color;
+    }
+
+    if let Some(button_emoji) = button_emoji {
+        current.button_emoji = clearable(button_emoji);
+    }
+
+    let appearance = current.clone();
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the appearance but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+    drop(config);
+
+    let now = Utc::now();
+    let thread_name = appearance.thread_name(PREVIEW_NUMBER, now, None, None);
+    let mut preview = appearance
+        .confession_embeds(PREVIEW_NUMBER, now, PREVIEW_CONTENT, None, None, None)
+        .into_iter()
+        .fold(CreateReply::default(), CreateReply::embed);
+    let mut components = vec![CreateActionRow::Buttons(vec![
+        reply_button(PREVIEW_NUMBER).disabled(true),
+    ])];
+
+    // The `/confessembed` message always looks like the default board
+    if board.is_none() {
+        preview = preview
+            .content(format!(
+                "Preview of confession threads named **{}** and of the `/confessembed` message. Buttons are disabled in the preview.",
+                thread_name
+            ))
+            .embed(appearance.button_message_embed());
+        components.push(CreateActionRow::Buttons(vec![
+            appearance.confess_button().disabled(true),
+        ]));
+    } else {
+        preview = preview.content(format!(
+            "Preview of confession threads named **{}**. Buttons are disabled in the preview.",
+            thread_name
+        ));
+    }
+
+    ctx.send(preview.components(components)).await?;
+
+    Ok(())
+}
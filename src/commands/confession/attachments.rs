@@ -0,0 +1,76 @@
+use crate::{Context, Error, config::AttachmentSettings};
+
+fn describe(settings: &AttachmentSettings) -> String {
+    format!(
+        "Images with `/confess`: {}\nMaximum size: {} MB\nImages need approval: {}",
+        if settings.enabled {
+            "allowed"
+        } else {
+            "not allowed"
+        },
+        settings.max_size_mb,
+        if settings.review { "yes" } else { "no" }
+    )
+}
+
+/// Configure whether images can be attached to confessions.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Configure whether images can be attached to confessions submitted with /confess."
+    )
+)]
+pub async fn attachments(
+    ctx: Context<'_>,
+    #[description = "Whether `/confess` accepts an image."] enabled: Option<bool>,
+    #[description = "Largest image accepted, in MB."]
+    #[min = 1]
+    #[max = 25]
+    max_size_mb: Option<u32>,
+    #[description = "Whether confessions with an image wait for moderator approval."]
+    review: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let guild_config = config.guild_mut(guild_id);
+    let settings = &mut guild_config.attachments;
+
+    if let Some(enabled) = enabled {
+        settings.enabled = enabled;
+    }
+
+    if let Some(max_size_mb) = max_size_mb {
+        settings.max_size_mb = max_size_mb;
+    }
+
+    if let Some(review) = review {
+        settings.review = review;
+    }
+
+    let mut summary = describe(settings);
+    if settings.enabled && settings.review && guild_config.review_channel.is_none() {
+        summary.push_str("\nImages need approval, but there is no review channel yet. Set one with `/set_review_channel`.");
+    }
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the image settings but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(summary).await?;
+    Ok(())
+}
@@ -0,0 +1,325 @@
+use std::time::Duration;
+
+use chrono::{Days, NaiveDate, NaiveTime};
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        self as serenity, ButtonStyle, Color, CreateActionRow, CreateAttachment, CreateButton,
+        CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+        CreateInteractionResponseMessage, Mentionable,
+    },
+};
+
+use super::ensure_moderator;
+use crate::{
+    Context, Error,
+    audit::{AuditQuery, AuditRecord, search},
+    sanitize::no_mentions,
+    utils::{AUDIT_BUTTON_PREFIX, truncate},
+};
+
+// Entries shown per page
+const PAGE_SIZE: usize = 10;
+
+// Most entries put into an export, newest first
+const MAX_EXPORT_ENTRIES: usize = 1000;
+
+// Columns of a CSV export, in the order of `AuditRecord`'s fields
+const CSV_HEADER: &str = "timestamp,kind,outcome,guild_id,channel_id,thread_id,confession_number,author_hash,content,sealed,redacted";
+
+/// File format of an audit log export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum ExportFormat {
+    #[name = "CSV"]
+    Csv,
+    #[name = "JSON"]
+    Json,
+}
+
+/// Quotes a CSV field if needed. Fields starting like a formula (including with a tab or
+/// carriage return) are prefixed with `'` so that spreadsheets don't run confessions as formulas.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn export_csv(records: &[AuditRecord]) -> String {
+    let mut csv = format!("{}\n", CSV_HEADER);
+
+    for record in records {
+        let id = |id: Option<serenity::ChannelId>| id.map(|id| id.to_string()).unwrap_or_default();
+        let fields = [
+            record.timestamp.to_rfc3339(),
+            record.kind.clone(),
+            record.outcome.clone(),
+            record.guild_id.to_string(),
+            id(record.channel_id),
+            id(record.thread_id),
+            record
+                .confession_number
+                .map(|number| number.to_string())
+                .unwrap_or_default(),
+            record.author_hash.clone().unwrap_or_default(),
+            record.content.clone().unwrap_or_default(),
+            record.sealed.clone().unwrap_or_default(),
+            record.redacted.to_string(),
+        ];
+
+        let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&line.join(","));
+        csv.push('\n');
+    }
+
+    csv
+}
+
+/// One entry of a page: when, what and where, followed by the start of the content.
+fn describe(record: &AuditRecord) -> String {
+    let subject = match (record.kind.as_str(), record.confession_number) {
+        ("reply", Some(number)) => format!("Reply to #{}", number),
+        ("reply", None) => "Reply".to_string(),
+        (_, Some(number)) => format!("Confession #{}", number),
+        (_, None) => "Confession".to_string(),
+    };
+
+    let mut header = format!(
+        "**<t:{}:f>** · {} · {}",
+        record.timestamp.timestamp(),
+        subject,
+        record.outcome.replace('_', " ")
+    );
+    if let Some(hash) = &record.author_hash {
+        header.push_str(&format!(" · `{}`", truncate(hash, 16)));
+    }
+    if let Some(channel) = record.thread_id.or(record.channel_id) {
+        header.push_str(&format!(" · {}", channel.mention()));
+    }
+
+    let content = match &record.content {
+        Some(content) => truncate(&content.replace('\n', " "), 200),
+        None if record.sealed.is_some() => "*encrypted*".to_string(),
+        None => "*redacted*".to_string(),
+    };
+
+    format!("{}\n> {}", header, content)
+}
+
+fn page_embed(records: &[AuditRecord], page: usize) -> CreateEmbed {
+    let pages = records.len().div_ceil(PAGE_SIZE);
+    let lines: Vec<String> = records
+        .iter()
+        .skip(page * PAGE_SIZE)
+        .take(PAGE_SIZE)
+        .map(describe)
+        .collect();
+
+    CreateEmbed::new()
+        .title(format!("Audit log ({} entries)", records.len()))
+        .description(truncate(&lines.join("\n\n"), 4096))
+        .color(Color::DARK_GREY)
+        .footer(CreateEmbedFooter::new(format!(
+            "Page {}/{} · newest first",
+            page + 1,
+            pages
+        )))
+}
+
+fn page_buttons(key: u64, page: usize, pages: usize) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(format!("{}:previous:{}", AUDIT_BUTTON_PREFIX, key))
+            .label("Previous")
+            .style(ButtonStyle::Secondary)
+            .disabled(page == 0),
+        CreateButton::new(format!("{}:next:{}", AUDIT_BUTTON_PREFIX, key))
+            .label("Next")
+            .style(ButtonStyle::Secondary)
+            .disabled(page + 1 >= pages),
+    ])]
+}
+
+/// Parses an optional `YYYY-MM-DD` date. Fails if a date was given but isn't valid.
+fn parse_date(input: Option<&str>) -> Result<Option<NaiveDate>, chrono::ParseError> {
+    input
+        .map(|input| NaiveDate::parse_from_str(input.trim(), "%Y-%m-%d"))
+        .transpose()
+}
+
+/// Search this server's audit log.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized(
+        "en-US",
+        "Search this server's audit log by author hash, date, number or text, or export it (moderators)."
+    )
+)]
+pub async fn audit(
+    ctx: Context<'_>,
+    #[description = "Only entries by this author hash (the start of it is enough)."]
+    author_hash: Option<String>,
+    #[description = "Only entries from this day on (YYYY-MM-DD, UTC)."] from: Option<String>,
+    #[description = "Only entries up to this day (YYYY-MM-DD, UTC)."] until: Option<String>,
+    #[description = "Only entries about this confession and its replies."] number: Option<i64>,
+    #[description = "Only entries whose content contains this text."] text: Option<String>,
+    #[description = "Send the entries as a file instead."] export: Option<ExportFormat>,
+) -> Result<(), Error> {
+    if !ensure_moderator(ctx).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let (Ok(from), Ok(until)) = (parse_date(from.as_deref()), parse_date(until.as_deref())) else {
+        ctx.say("Dates must be written as YYYY-MM-DD, e.g. 2026-01-31.")
+            .await?;
+        return Ok(());
+    };
+
+    let query = AuditQuery {
+        guild_id,
+        author_hash,
+        from: from.map(|date| date.and_time(NaiveTime::MIN).and_utc()),
+        // The whole last day is included
+        until: until
+            .and_then(|date| date.checked_add_days(Days::new(1)))
+            .map(|date| date.and_time(NaiveTime::MIN).and_utc()),
+        confession_number: number,
+        text,
+    };
+
+    let directory = ctx.data().config.read().await.logging.directory.clone();
+    let records = tokio::task::spawn_blocking(move || search(&directory, &query)).await??;
+
+    if records.is_empty() {
+        ctx.say("No audit entries match these filters.").await?;
+        return Ok(());
+    }
+
+    if let Some(format) = export {
+        let exported = &records[..records.len().min(MAX_EXPORT_ENTRIES)];
+        let attachment = match format {
+            ExportFormat::Csv => {
+                CreateAttachment::bytes(export_csv(exported).into_bytes(), "audit.csv")
+            }
+            ExportFormat::Json => {
+                CreateAttachment::bytes(serde_json::to_vec_pretty(exported)?, "audit.json")
+            }
+        };
+
+        let content = if records.len() > exported.len() {
+            format!(
+                "{} entries match; the newest {} are exported. Narrow the filters to export the others.",
+                records.len(),
+                exported.len()
+            )
+        } else {
+            format!("Exported {} audit entries.", records.len())
+        };
+
+        ctx.send(
+            CreateReply::default()
+                .content(content)
+                .attachment(attachment),
+        )
+        .await?;
+        return Ok(());
+    }
+
+    // Page through the entries with buttons until they haven't been used for 10 minutes
+    let key = ctx.id();
+    let pages = records.len().div_ceil(PAGE_SIZE);
+    let mut page = 0;
+
+    let reply = ctx
+        .send(
+            CreateReply::default()
+                .embed(page_embed(&records, page))
+                .components(page_buttons(key, page, pages)),
+        )
+        .await?;
+
+    if pages == 1 {
+        return Ok(());
+    }
+
+    let suffix = format!(":{}", key);
+    while let Some(interaction) =
+        serenity::collector::ComponentInteractionCollector::new(&ctx.serenity_context().shard)
+            .filter({
+                let suffix = suffix.clone();
+                move |interaction| {
+                    interaction.data.custom_id.starts_with(AUDIT_BUTTON_PREFIX)
+                        && interaction.data.custom_id.ends_with(&suffix)
+                }
+            })
+            .timeout(Duration::from_secs(600))
+            .await
+    {
+        page = match interaction.data.custom_id.split(':').nth(1) {
+            Some("previous") => page.saturating_sub(1),
+            _ => (page + 1).min(pages - 1),
+        };
+
+        interaction
+            .create_response(
+                ctx.http(),
+                CreateInteractionResponse::UpdateMessage(
+                    CreateInteractionResponseMessage::new()
+                        .allowed_mentions(no_mentions())
+                        .embed(page_embed(&records, page))
+                        .components(page_buttons(key, page, pages)),
+                ),
+            )
+            .await?;
+    }
+
+    reply
+        .edit(
+            ctx,
+            CreateReply::default()
+                .allowed_mentions(no_mentions())
+                .embed(page_embed(&records, page))
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_are_quoted_when_needed() {
+        assert_eq!(csv_field("plain text"), "plain text");
+        assert_eq!(csv_field("a, b"), "\"a, b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field(""), "");
+    }
+
+    #[test]
+    fn csv_fields_starting_like_a_formula_are_neutralised() {
+        assert_eq!(csv_field("=1+1"), "'=1+1");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-1"), "'-1");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("\t=1+1"), "'\t=1+1");
+        assert_eq!(csv_field("\r=1+1"), "\"'\r=1+1\"");
+        assert_eq!(csv_field("=HYPERLINK(\"x\")"), "\"'=HYPERLINK(\"\"x\"\")\"");
+        assert_eq!(csv_field("1=1"), "1=1");
+    }
+}
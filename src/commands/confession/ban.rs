@@ -0,0 +1,240 @@
+use chrono::Utc;
+use poise::serenity_prelude::{Color, CreateEmbed, GuildId, Mentionable, UserId};
+
+use super::ensure_moderator;
+use crate::{
+    Context, Data, Error,
+    storage::Ban,
+    utils::{parse_duration, truncate},
+};
+
+/// Returns the message to show a user who is banned from confessing in the guild, if they are.
+pub async fn ban_notice(
+    data: &Data,
+    guild_id: GuildId,
+    user_id: UserId,
+) -> Result<Option<String>, Error> {
+    let author_hashes = data.config.read().await.author_hashes(guild_id, user_id);
+
+    let Some(ban) = data.storage.find_active_ban(guild_id, &author_hashes)? else {
+        return Ok(None);
+    };
+
+    let mut notice = match ban.expires_at {
+        Some(expires_at) => format!(
+            "You have been banned from submitting confessions in this server until <t:{}:f>.",
+            expires_at.timestamp()
+        ),
+        None => "You have been permanently banned from submitting confessions in this server."
+            .to_string(),
+    };
+
+    if let Some(reason) = ban.reason {
+        notice.push_str(&format!("\nReason: {}", reason));
+    }
+
+    Ok(Some(notice))
+}
+
+/// Ban the author of a confession from submitting new confessions.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized(
+        "en-US",
+        "Ban the author of a confession from submitting new confessions in this server."
+    )
+)]
+pub async fn ban(
+    ctx: Context<'_>,
+    #[description = "The number of the confession whose author should be banned."]
+    #[min = 1]
+    number: i64,
+    #[description = "How long the ban lasts, e.g. 30m, 12h, 7d or 2w. Permanent if empty."]
+    duration: Option<String>,
+    #[description = "Why the author is being banned (shown to them when they try to confess)."]
+    #[max_length = 500]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    if !ensure_moderator(ctx).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let expires_at = match duration.as_deref().map(parse_duration) {
+        None => None,
+        Some(Some(duration)) => match Utc::now().checked_add_signed(duration) {
+            Some(expires_at) => Some(expires_at),
+            None => {
+                ctx.say("Error: That duration is too long. Leave it out for a permanent ban.")
+                    .await?;
+                return Ok(());
+            }
+        },
+        Some(None) => {
+            ctx.say("Error: Invalid duration. Use a number followed by m, h, d or w, e.g. `12h` or `7d`.")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data();
+
+    let Some(confession) = data.storage.get_confession(guild_id, number)? else {
+        ctx.say(format!("Confession #{} does not exist.", number))
+            .await?;
+        return Ok(());
+    };
+
+    let ban = Ban {
+        confession_number: number,
+        reason,
+        banned_by: ctx.author().id,
+        expires_at,
+    };
+    data.storage
+        .insert_ban(guild_id, &confession.author_hash, &ban)?;
+
+    let until = match expires_at {
+        Some(expires_at) => format!("until <t:{}:f>", expires_at.timestamp()),
+        None => "permanently".to_string(),
+    };
+
+    data.storage.append_moderation_audit(
+        guild_id,
+        "author_banned",
+        ctx.author().id,
+        Some(number),
+        &format!(
+            "banned {}, reason: {}",
+            until,
+            ban.reason.as_deref().unwrap_or("none")
+        ),
+    )?;
+
+    ctx.say(format!(
+        "The author of confession #{} has been banned from submitting confessions {}.",
+        number, until
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// Lift the ban on the author of a confession.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "Lift the ban on the author of a confession.")
+)]
+pub async fn unban(
+    ctx: Context<'_>,
+    #[description = "The number of the confession whose author should be unbanned."]
+    #[min = 1]
+    number: i64,
+) -> Result<(), Error> {
+    if !ensure_moderator(ctx).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+    let data = ctx.data();
+
+    let Some(confession) = data.storage.get_confession(guild_id, number)? else {
+        ctx.say(format!("Confession #{} does not exist.", number))
+            .await?;
+        return Ok(());
+    };
+
+    let removed = data
+        .storage
+        .delete_bans(guild_id, &confession.author_hash)?;
+
+    if removed == 0 {
+        ctx.say(format!(
+            "The author of confession #{} is not banned.",
+            number
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    data.storage.append_moderation_audit(
+        guild_id,
+        "author_unbanned",
+        ctx.author().id,
+        Some(number),
+        &format!("lifted {} ban(s)", removed),
+    )?;
+
+    ctx.say(format!(
+        "The author of confession #{} can submit confessions again.",
+        number
+    ))
+    .await?;
+
+    Ok(())
+}
+
+/// List the active confession bans of this server.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized("en-US", "List the active confession bans of this server.")
+)]
+pub async fn bans(ctx: Context<'_>) -> Result<(), Error> {
+    if !ensure_moderator(ctx).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+    let bans = ctx.data().storage.active_bans(guild_id)?;
+
+    if bans.is_empty() {
+        ctx.say("There are no active confession bans.").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = bans
+        .iter()
+        .map(|ban| {
+            let until = match ban.expires_at {
+                Some(expires_at) => format!("until <t:{}:f>", expires_at.timestamp()),
+                None => "permanent".to_string(),
+            };
+
+            format!(
+                "**Confession #{}** ({}) by {}{}",
+                ban.confession_number,
+                until,
+                ban.banned_by.mention(),
+                ban.reason
+                    .as_deref()
+                    .map(|reason| format!(": {}", reason))
+                    .unwrap_or_default()
+            )
+        })
+        .collect();
+
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::new()
+                .title(format!("Active confession bans ({})", bans.len()))
+                .description(truncate(&lines.join("\n"), 4096))
+                .color(Color::RED),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
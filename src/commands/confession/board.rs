@@ -0,0 +1,190 @@
+use poise::serenity_prelude::{self as serenity, ChannelId, Mentionable};
+
+use crate::{
+    Context, Error,
+    commands::{
+        board::{autocomplete_board, board_name, validate_board_name},
+        category::reconcile_forum_tags,
+    },
+    config::{Board, ForumTags},
+};
+
+/// Manage the named confession boards of this server.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands("set", "remove", "list"),
+    subcommand_required
+)]
+pub async fn board(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Create a board or move it to another channel.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Create a named confession board or move an existing one to another channel."
+    )
+)]
+pub async fn set(
+    ctx: Context<'_>,
+    #[description = "Name of the board, e.g. vent."]
+    #[autocomplete = "autocomplete_board"]
+    #[max_length = 32]
+    name: String,
+    #[description = "The channel (Text or Forum) where the board's confessions are posted."]
+    channel: ChannelId,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let name = board_name(&name);
+    if let Err(e) = validate_board_name(&name) {
+        ctx.say(e).await?;
+        return Ok(());
+    }
+
+    let guild_channel = match channel.to_channel(ctx.http()).await? {
+        serenity::Channel::Guild(guild_channel)
+            if matches!(
+                guild_channel.kind,
+                serenity::ChannelType::Text | serenity::ChannelType::Forum
+            ) =>
+        {
+            guild_channel
+        }
+        _ => {
+            ctx.say("Error: The provided channel must be a Text channel or a Forum channel.")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let guild_config = config.guild_mut(guild_id);
+
+    // New boards start out with the default board's settings, except for its forum tags
+    let default_settings = guild_config.settings.clone();
+    let board = guild_config.boards.entry(name.clone()).or_insert_with(|| {
+        let mut settings = default_settings;
+        settings.forum_tags = ForumTags::default();
+        Board { channel, settings }
+    });
+    board.channel = channel;
+
+    let tag_notes = reconcile_forum_tags(&guild_channel, &mut board.settings.forum_tags);
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the board but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(format!(
+        "Confessions on the board `{}` are now posted in {}. Pass `board:{}` to the other `/confession` settings commands to customise it, and run `/confessembed` again to offer it on the confession message.{}",
+        name,
+        channel.mention(),
+        name,
+        tag_notes
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Delete a board.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized("en-US", "Delete a named confession board and its settings.")
+)]
+pub async fn remove(
+    ctx: Context<'_>,
+    #[description = "Name of the board to delete."]
+    #[autocomplete = "autocomplete_board"]
+    name: String,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let name = board_name(&name);
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+
+    if config.guild_mut(guild_id).boards.remove(&name).is_none() {
+        ctx.say(format!("There is no board called `{}`.", name))
+            .await?;
+        return Ok(());
+    }
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Deleted the board but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(format!(
+        "Deleted the board `{}`. Its confessions stay where they are; run `/confessembed` again to update the confession message.",
+        name
+    ))
+    .await?;
+    Ok(())
+}
+
+/// List the boards of this server.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized("en-US", "List the confession boards of this server.")
+)]
+pub async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let (default_channel, boards) = {
+        let config = ctx.data().config.read().await;
+        (
+            config.confession_threads.get(&guild_id).copied(),
+            config.guild(guild_id).boards,
+        )
+    };
+
+    let lines: Vec<String> = std::iter::once(format!(
+        "Default board: {}",
+        default_channel.map_or("not set up".to_string(), |channel| channel
+            .mention()
+            .to_string())
+    ))
+    .chain(
+        boards
+            .iter()
+            .map(|(name, board)| format!("`{}`: {}", name, board.channel.mention())),
+    )
+    .collect();
+
+    ctx.say(lines.join("\n")).await?;
+    Ok(())
+}
@@ -0,0 +1,253 @@
+use poise::serenity_prelude::{
+    self as serenity, AutocompleteChoice, ChannelId, Color, CreateEmbed, EditMessage, EditThread,
+    MessageId,
+};
+
+use crate::{
+    Context, Error,
+    logging::{AuditEntry, AuditOutcome},
+    sanitize::no_mentions,
+    utils::truncate,
+};
+
+// How many of their own confessions are offered to the author
+const RECENT_CONFESSIONS: u32 = 25;
+
+/// Offers the confessions the user wrote recently, without anyone else seeing them.
+async fn autocomplete_own_confession(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let Some(guild_id) = ctx.guild_id() else {
+        return Vec::new();
+    };
+
+    let data = ctx.data();
+    let author_hashes = data
+        .config
+        .read()
+        .await
+        .author_hashes(guild_id, ctx.author().id);
+
+    let confessions =
+        match data
+            .storage
+            .recent_confessions_by(guild_id, &author_hashes, RECENT_CONFESSIONS)
+        {
+            Ok(confessions) => confessions,
+            Err(e) => {
+                log::error!("Failed to list confessions for deletion: {:?}", e);
+                return Vec::new();
+            }
+        };
+
+    confessions
+        .into_iter()
+        .filter(|confession| {
+            confession
+                .number
+                .to_string()
+                .starts_with(partial.trim_start_matches('#'))
+        })
+        .map(|confession| {
+            AutocompleteChoice::new(
+                truncate(
+                    &format!(
+                        "#{}: {}",
+                        confession.number,
+                        confession.preview.replace('\n', " ")
+                    ),
+                    100,
+                ),
+                confession.number,
+            )
+        })
+        .collect()
+}
+
+/// Delete one of your own confessions. Nobody learns that you wrote it.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    description_localized(
+        "en-US",
+        "Delete one of your own recent confessions. Nobody learns that you wrote it."
+    )
+)]
+pub async fn delete(
+    ctx: Context<'_>,
+    #[description = "The confession to delete."]
+    #[autocomplete = "autocomplete_own_confession"]
+    #[min = 1]
+    number: i64,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let data = ctx.data();
+    let author_hashes = data
+        .config
+        .read()
+        .await
+        .author_hashes(guild_id, ctx.author().id);
+
+    // Answer the same way whether the confession doesn't exist or belongs to someone else
+    let confession = data
+        .storage
+        .get_confession(guild_id, number)?
+        .filter(|confession| author_hashes.contains(&confession.author_hash));
+    let Some(confession) = confession else {
+        ctx.say(format!(
+            "Confession #{} is not one of your recent confessions.",
+            number
+        ))
+        .await?;
+        return Ok(());
+    };
+
+    if confession.deleted {
+        ctx.say(format!("Confession #{} has already been deleted.", number))
+            .await?;
+        return Ok(());
+    }
+
+    let Some(message_id) = confession.message_id else {
+        ctx.say(format!(
+            "Confession #{} has not been posted yet and cannot be deleted.",
+            number
+        ))
+        .await?;
+        return Ok(());
+    };
+
+    ctx.defer_ephemeral().await?;
+
+    let removed = match confession.thread_id {
+        Some(thread_id) => {
+            remove_confession_thread(
+                ctx.http(),
+                thread_id,
+                message_id,
+                &confession.part_message_ids,
+            )
+            .await
+        }
+        // Confessions posted without a thread are messages in the confession channel
+        None => match confession
+            .channel_id
+            .delete_message(ctx.http(), message_id)
+            .await
+        {
+            Ok(()) => {
+                delete_parts(
+                    ctx.http(),
+                    confession.channel_id,
+                    &confession.part_message_ids,
+                )
+                .await;
+                true
+            }
+            Err(e) => {
+                log::error!(
+                    "Failed to delete confession message {}: {:?}",
+                    message_id,
+                    e
+                );
+                false
+            }
+        },
+    };
+
+    if !removed {
+        ctx.say("An error occurred while deleting your confession. Please ask a staff member to remove it.")
+            .await?;
+        return Ok(());
+    }
+
+    data.storage.mark_confession_deleted(guild_id, number)?;
+    log::warn!(
+        "Confession #{} in guild {} deleted by its author",
+        number,
+        guild_id
+    );
+
+    let audit_key = data.config.read().await.audit_key();
+    AuditEntry::confession(
+        guild_id,
+        &confession.author_hash,
+        &confession.preview,
+        AuditOutcome::Deleted,
+    )
+    .number(number)
+    .posted_in(confession.channel_id, confession.thread_id)
+    .log(audit_key.as_ref());
+
+    ctx.say(format!("Confession #{} has been deleted.", number))
+        .await?;
+    Ok(())
+}
+
+/// Deletes the messages holding the second and following parts of a long confession.
+async fn delete_parts(
+    http: &serenity::Http,
+    channel_id: ChannelId,
+    part_message_ids: &[MessageId],
+) {
+    for &part_message_id in part_message_ids {
+        if let Err(e) = channel_id.delete_message(http, part_message_id).await {
+            log::error!(
+                "Failed to delete confession part message {}: {:?}",
+                part_message_id,
+                e
+            );
+        }
+    }
+}
+
+/// Deletes the confession's thread or forum post. If the bot may not delete it, the confession
+/// message is replaced with a notice, its other parts are deleted and the thread is locked
+/// instead.
+async fn remove_confession_thread(
+    http: &serenity::Http,
+    thread_id: ChannelId,
+    message_id: MessageId,
+    part_message_ids: &[MessageId],
+) -> bool {
+    let Err(e) = thread_id.delete(http).await else {
+        return true;
+    };
+    log::warn!(
+        "Failed to delete confession thread {}, locking it instead: {:?}",
+        thread_id,
+        e
+    );
+
+    let notice = EditMessage::new()
+        .embed(
+            CreateEmbed::new()
+                .title("Deleted Confession")
+                .description("This confession was deleted by its author.")
+                .color(Color::DARK_GREY),
+        )
+        .components(vec![])
+        .allowed_mentions(no_mentions());
+
+    if let Err(e) = thread_id.edit_message(http, message_id, notice).await {
+        log::error!(
+            "Failed to remove the content of confession message {}: {:?}",
+            message_id,
+            e
+        );
+        return false;
+    }
+
+    delete_parts(http, thread_id, part_message_ids).await;
+
+    if let Err(e) = thread_id
+        .edit_thread(http, EditThread::new().locked(true).archived(true))
+        .await
+    {
+        log::error!("Failed to lock confession thread {}: {:?}", thread_id, e);
+    }
+
+    true
+}
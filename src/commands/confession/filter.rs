@@ -0,0 +1,441 @@
+use poise::{
+    ChoiceParameter,
+    serenity_prelude::{Color, CreateEmbed},
+};
+
+use crate::{
+    Context, Error,
+    commands::board::{autocomplete_board, board_name},
+    filter::{self, FilterAction, FilterConfig, LinkPolicy, RegexRule, Verdict, WordRule},
+    sanitize::{MentionMode, sanitize_mentions},
+    utils::truncate,
+};
+
+/// Manage the content filter applied to new confessions.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands(
+        "add_word",
+        "remove_word",
+        "add_regex",
+        "remove_regex",
+        "links",
+        "mentions",
+        "list",
+        "test"
+    ),
+    subcommand_required
+)]
+pub async fn filter(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Applies a change to the filter rules of a board, saves the configuration and reports back.
+async fn update_filter(
+    ctx: Context<'_>,
+    board: Option<String>,
+    change: impl FnOnce(&mut FilterConfig) -> String,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+    let board = board.as_deref().map(board_name);
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let Some(settings) = config
+        .guild_mut(guild_id)
+        .board_settings_mut(board.as_deref())
+    else {
+        ctx.say(format!(
+            "There is no board called `{}`.",
+            board.unwrap_or_default()
+        ))
+        .await?;
+        return Ok(());
+    };
+    let reply = change(&mut settings.filter);
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the content filter but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Returns the filter rules of a board, or replies that there is no such board.
+async fn board_filter(
+    ctx: Context<'_>,
+    board: Option<String>,
+) -> Result<Option<FilterConfig>, Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+    let board = board.as_deref().map(board_name);
+
+    let filter = ctx
+        .data()
+        .config
+        .read()
+        .await
+        .guild(guild_id)
+        .board_settings(board.as_deref())
+        .map(|settings| settings.filter.clone());
+
+    if filter.is_none() {
+        ctx.say(format!(
+            "There is no board called `{}`.",
+            board.unwrap_or_default()
+        ))
+        .await?;
+    }
+
+    Ok(filter)
+}
+
+/// Block a word or phrase in new confessions.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    rename = "add-word",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn add_word(
+    ctx: Context<'_>,
+    #[description = "The word or phrase to block (case-insensitive)."]
+    #[max_length = 100]
+    word: String,
+    #[description = "What to do with confessions containing it."] action: FilterAction,
+    #[description = "Board to configure (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    let word = word.trim().to_string();
+
+    update_filter(ctx, board, |filter| {
+        filter
+            .blocked_words
+            .retain(|rule| !rule.word.eq_ignore_ascii_case(&word));
+        filter.blocked_words.push(WordRule {
+            word: word.clone(),
+            action,
+        });
+
+        format!("Blocked \"{}\" ({}).", word, action.name())
+    })
+    .await
+}
+
+/// Stop blocking a word or phrase.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    rename = "remove-word",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn remove_word(
+    ctx: Context<'_>,
+    #[description = "The blocked word or phrase to remove."] word: String,
+    #[description = "Board to configure (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    let word = word.trim().to_string();
+
+    update_filter(ctx, board, |filter| {
+        let before = filter.blocked_words.len();
+        filter
+            .blocked_words
+            .retain(|rule| !rule.word.eq_ignore_ascii_case(&word));
+
+        if filter.blocked_words.len() == before {
+            format!("\"{}\" is not blocked.", word)
+        } else {
+            format!("\"{}\" is no longer blocked.", word)
+        }
+    })
+    .await
+}
+
+/// Add a regular expression rule for new confessions.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    rename = "add-regex",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn add_regex(
+    ctx: Context<'_>,
+    #[description = "The regular expression to match (case-insensitive)."]
+    #[max_length = 300]
+    pattern: String,
+    #[description = "What to do with confessions matching it."] action: FilterAction,
+    #[description = "Board to configure (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    if let Err(e) = filter::compile_pattern(&pattern) {
+        ctx.say(format!("Error: Invalid regular expression: {}", e))
+            .await?;
+        return Ok(());
+    }
+
+    update_filter(ctx, board, |filter| {
+        filter.regex_rules.retain(|rule| rule.pattern != pattern);
+        filter.regex_rules.push(RegexRule {
+            pattern: pattern.clone(),
+            action,
+        });
+
+        format!("Added the rule `{}` ({}).", pattern, action.name())
+    })
+    .await
+}
+
+/// Remove a regular expression rule.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    rename = "remove-regex",
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn remove_regex(
+    ctx: Context<'_>,
+    #[description = "The regular expression to remove, exactly as it was added."] pattern: String,
+    #[description = "Board to configure (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    update_filter(ctx, board, |filter| {
+        let before = filter.regex_rules.len();
+        filter.regex_rules.retain(|rule| rule.pattern != pattern);
+
+        if filter.regex_rules.len() == before {
+            format!("There is no rule `{}`.", pattern)
+        } else {
+            format!("Removed the rule `{}`.", pattern)
+        }
+    })
+    .await
+}
+
+/// Choose how links and Discord invites in confessions are treated.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn links(
+    ctx: Context<'_>,
+    #[description = "What to do with links."] links: Option<LinkPolicy>,
+    #[description = "What to do with Discord invites."] invites: Option<LinkPolicy>,
+    #[description = "Board to configure (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    update_filter(ctx, board, |filter| {
+        if let Some(links) = links {
+            filter.link_policy = links;
+        }
+
+        if let Some(invites) = invites {
+            filter.invite_policy = invites;
+        }
+
+        format!(
+            "Links: {}\nDiscord invites: {}",
+            filter.link_policy.name(),
+            filter.invite_policy.name()
+        )
+    })
+    .await
+}
+
+/// Choose which mentions confessions may contain and how the rest are neutralised.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn mentions(
+    ctx: Context<'_>,
+    #[description = "Maximum user mentions per confession (0 for unlimited)."]
+    #[max = 50]
+    max_mentions: Option<u32>,
+    #[description = "Whether @everyone and @here are allowed."] allow_mass_mentions: Option<bool>,
+    #[description = "Whether role mentions are allowed."] allow_role_mentions: Option<bool>,
+    #[description = "What to do with confessions breaking these rules."] action: Option<
+        FilterAction,
+    >,
+    #[description = "Whether remaining mentions are shown as plain text or removed."] mode: Option<
+        MentionMode,
+    >,
+    #[description = "Board to configure (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    update_filter(ctx, board, |filter| {
+        if let Some(max_mentions) = max_mentions {
+            filter.max_mentions = max_mentions;
+        }
+
+        if let Some(allow_mass_mentions) = allow_mass_mentions {
+            filter.allow_mass_mentions = allow_mass_mentions;
+        }
+
+        if let Some(allow_role_mentions) = allow_role_mentions {
+            filter.allow_role_mentions = allow_role_mentions;
+        }
+
+        if let Some(action) = action {
+            filter.mention_action = action;
+        }
+
+        if let Some(mode) = mode {
+            filter.mention_mode = mode;
+        }
+
+        describe_mentions(filter)
+    })
+    .await
+}
+
+fn describe_mentions(filter: &FilterConfig) -> String {
+    format!(
+        "Maximum user mentions: {}\n@everyone/@here: {}\nRole mentions: {}\nAction: {}\nRemaining mentions: {}",
+        match filter.max_mentions {
+            0 => "unlimited".to_string(),
+            max => max.to_string(),
+        },
+        if filter.allow_mass_mentions {
+            "allowed"
+        } else {
+            "not allowed"
+        },
+        if filter.allow_role_mentions {
+            "allowed"
+        } else {
+            "not allowed"
+        },
+        filter.mention_action.name(),
+        match filter.mention_mode {
+            MentionMode::Escape => "shown as plain text",
+            MentionMode::Strip => "removed",
+        }
+    )
+}
+
+/// Show the content filter rules of this server.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn list(
+    ctx: Context<'_>,
+    #[description = "Board to show (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    let Some(filter) = board_filter(ctx, board).await? else {
+        return Ok(());
+    };
+
+    let list_or_none = |lines: Vec<String>| {
+        if lines.is_empty() {
+            "None".to_string()
+        } else {
+            truncate(&lines.join("\n"), 1024)
+        }
+    };
+
+    let words = filter
+        .blocked_words
+        .iter()
+        .map(|rule| format!("\"{}\" ({})", rule.word, rule.action.name()))
+        .collect();
+    let regexes = filter
+        .regex_rules
+        .iter()
+        .map(|rule| format!("`{}` ({})", rule.pattern, rule.action.name()))
+        .collect();
+
+    ctx.send(
+        poise::CreateReply::default().embed(
+            CreateEmbed::new()
+                .title("Content filter")
+                .field("Blocked words", list_or_none(words), false)
+                .field("Regex rules", list_or_none(regexes), false)
+                .field(
+                    "Links",
+                    format!(
+                        "Links: {}\nDiscord invites: {}",
+                        filter.link_policy.name(),
+                        filter.invite_policy.name()
+                    ),
+                    false,
+                )
+                .field("Mentions", describe_mentions(&filter), false)
+                .color(Color::from_rgb(255, 165, 0)),
+        ),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Check what the content filter would do with a piece of text.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD"
+)]
+pub async fn test(
+    ctx: Context<'_>,
+    #[description = "The text to run through the filter."] text: String,
+    #[description = "Board whose filter to use (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    let Some(filter_config) = board_filter(ctx, board).await? else {
+        return Ok(());
+    };
+
+    let outcome = filter::apply(&filter_config, &text);
+    let content = sanitize_mentions(&outcome.content, filter_config.mention_mode);
+    let mut verdict = match outcome.verdict {
+        Verdict::Allow => "Allowed".to_string(),
+        Verdict::Hold(reason) => format!("Held for review ({})", reason),
+        Verdict::Reject(reason) => format!("Rejected ({})", reason),
+    };
+
+    if let Some(reason) = outcome.needs_warning {
+        verdict.push_str(&format!(", needs a content warning ({})", reason));
+    }
+
+    ctx.say(format!(
+        "**Result:** {}\n**Posted as:**\n{}",
+        verdict,
+        truncate(&content, 1800)
+    ))
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,117 @@
+use poise::ChoiceParameter;
+
+use crate::{
+    Context, Error,
+    config::{FieldVisibility, FormField, FormSettings},
+};
+
+fn describe(form: &FormSettings) -> String {
+    let field = |field: &FormField| match field.visibility {
+        FieldVisibility::Hidden => "hidden".to_string(),
+        visibility => format!(
+            "{}, up to {} characters",
+            visibility.name().to_lowercase(),
+            field.max_length
+        ),
+    };
+
+    format!(
+        "Confession: up to {} characters\nTitle: {}\nContent warning: {}\nSignature: {}\nPreview before submitting: {}",
+        form.content_max_length,
+        field(&form.title),
+        field(&form.content_warning),
+        field(&form.signature),
+        if form.preview { "yes" } else { "no" }
+    )
+}
+
+/// Configure which fields the confession form asks for.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Configure the title, content warning and signature fields of the confession form."
+    )
+)]
+#[allow(clippy::too_many_arguments)]
+pub async fn form(
+    ctx: Context<'_>,
+    #[description = "Longest confession accepted, in characters (over 4000 adds a second box)."]
+    #[min = 100]
+    #[max = 8000]
+    content_max_length: Option<u16>,
+    #[description = "Whether authors can give their confession a title (used as the thread name)."]
+    title: Option<FieldVisibility>,
+    #[description = "Longest title accepted, in characters."]
+    #[min = 1]
+    #[max = 100]
+    title_max_length: Option<u16>,
+    #[description = "Whether authors can add a content warning."] content_warning: Option<
+        FieldVisibility,
+    >,
+    #[description = "Longest content warning accepted, in characters."]
+    #[min = 1]
+    #[max = 100]
+    content_warning_max_length: Option<u16>,
+    #[description = "Whether authors can sign their confession with a pseudonym."]
+    signature: Option<FieldVisibility>,
+    #[description = "Longest signature accepted, in characters."]
+    #[min = 1]
+    #[max = 80]
+    signature_max_length: Option<u16>,
+    #[description = "Whether authors see a preview of their confession before submitting it."]
+    preview: Option<bool>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let form = &mut config.guild_mut(guild_id).form;
+
+    if let Some(content_max_length) = content_max_length {
+        form.content_max_length = content_max_length;
+    }
+
+    for (field, visibility, max_length) in [
+        (&mut form.title, title, title_max_length),
+        (
+            &mut form.content_warning,
+            content_warning,
+            content_warning_max_length,
+        ),
+        (&mut form.signature, signature, signature_max_length),
+    ] {
+        if let Some(visibility) = visibility {
+            field.visibility = visibility;
+        }
+
+        if let Some(max_length) = max_length {
+            field.max_length = max_length;
+        }
+    }
+
+    if let Some(preview) = preview {
+        form.preview = preview;
+    }
+
+    let summary = describe(form);
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the confession form but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(summary).await?;
+    Ok(())
+}
@@ -0,0 +1,91 @@
+use crate::{Context, Error, config::RateLimits};
+
+fn describe(limits: &RateLimits) -> String {
+    let cooldown = match limits.user_cooldown_minutes {
+        0 => "off".to_string(),
+        minutes => format!("{} minutes", minutes),
+    };
+
+    let daily_limit = match limits.user_daily_limit {
+        0 => "off".to_string(),
+        confessions => format!("{} confessions", confessions),
+    };
+
+    // The burst limit needs both a number of confessions and a window to count them in
+    let burst_limit = match (limits.guild_burst_limit, limits.guild_burst_window_minutes) {
+        (0, _) | (_, 0) => "off".to_string(),
+        (confessions, minutes) => format!("{} confessions per {} minutes", confessions, minutes),
+    };
+
+    format!(
+        "Cooldown per user: {}\nDaily limit per user: {}\nServer-wide burst limit: {}",
+        cooldown, daily_limit, burst_limit
+    )
+}
+
+/// Configure how often confessions can be submitted. Use 0 to turn a limit off.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Configure how often confessions can be submitted (0 turns a limit off)."
+    )
+)]
+pub async fn limits(
+    ctx: Context<'_>,
+    #[description = "Minutes a user has to wait between two confessions."]
+    #[max = 1440]
+    user_cooldown_minutes: Option<u32>,
+    #[description = "Maximum confessions per user within 24 hours."]
+    #[max = 1000]
+    user_daily_limit: Option<u32>,
+    #[description = "Maximum confessions for the whole server within the burst window."]
+    #[max = 1000]
+    guild_burst_limit: Option<u32>,
+    #[description = "Length of the server-wide burst window in minutes."]
+    #[max = 1440]
+    guild_burst_window_minutes: Option<u32>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let limits = &mut config.guild_mut(guild_id).rate_limits;
+
+    if let Some(user_cooldown_minutes) = user_cooldown_minutes {
+        limits.user_cooldown_minutes = user_cooldown_minutes;
+    }
+
+    if let Some(user_daily_limit) = user_daily_limit {
+        limits.user_daily_limit = user_daily_limit;
+    }
+
+    if let Some(guild_burst_limit) = guild_burst_limit {
+        limits.guild_burst_limit = guild_burst_limit;
+    }
+
+    if let Some(guild_burst_window_minutes) = guild_burst_window_minutes {
+        limits.guild_burst_window_minutes = guild_burst_window_minutes;
+    }
+
+    let summary = describe(limits);
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the rate limits but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(summary).await?;
+    Ok(())
+}
@@ -0,0 +1,145 @@
+pub mod appearance;
+pub mod attachments;
+pub mod audit;
+pub mod ban;
+pub mod board;
+pub mod delete;
+pub mod filter;
+pub mod form;
+pub mod limits;
+pub mod purge_audit;
+pub mod reveal;
+pub mod schedule;
+pub mod tags;
+pub mod threads;
+
+use poise::{
+    CreateReply,
+    serenity_prelude::{Member, Mentionable, RoleId},
+};
+
+use crate::{Context, Error};
+
+/// Confession moderation commands.
+#[poise::command(
+    slash_command,
+    guild_only,
+    subcommands(
+        "reveal::reveal",
+        "ban::ban",
+        "ban::unban",
+        "ban::bans",
+        "limits::limits",
+        "filter::filter",
+        "delete::delete",
+        "appearance::appearance",
+        "threads::threads",
+        "tags::tags",
+        "board::board",
+        "attachments::attachments",
+        "form::form",
+        "schedule::schedule",
+        "purge_audit::purge_audit",
+        "audit::audit",
+        "moderation"
+    ),
+    subcommand_required
+)]
+pub async fn confession(_ctx: Context<'_>) -> Result<(), Error> {
+    Ok(())
+}
+
+/// Returns whether a member holds the guild's moderator role.
+pub fn is_moderator(member: Option<&Member>, moderator_role: Option<RoleId>) -> bool {
+    match (member, moderator_role) {
+        (Some(member), Some(role)) => member.roles.contains(&role),
+        _ => false,
+    }
+}
+
+/// Checks that the invoking member holds the guild's moderator role.
+/// Replies with an explanation and returns `false` if they don't.
+pub async fn ensure_moderator(ctx: Context<'_>) -> Result<bool, Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let moderator_role = ctx
+        .data()
+        .config
+        .read()
+        .await
+        .guild(guild_id)
+        .moderator_role;
+
+    let reply = match moderator_role {
+        None => "No moderator role has been set up for this guild. Please ask an administrator to use `/confession moderation`.".to_string(),
+        Some(role) if !is_moderator(ctx.author_member().await.as_deref(), Some(role)) => {
+            format!("You need the {} role to use this command.", role.mention())
+        }
+        Some(_) => return Ok(true),
+    };
+
+    ctx.send(CreateReply::default().content(reply).ephemeral(true))
+        .await?;
+    Ok(false)
+}
+
+/// Configure the moderator role and the reveal approval window.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Configure the moderator role and how long reveal requests wait for approval."
+    )
+)]
+pub async fn moderation(
+    ctx: Context<'_>,
+    #[description = "The role allowed to use the confession moderation commands."]
+    moderator_role: Option<RoleId>,
+    #[description = "How many minutes a reveal request waits for a second moderator."]
+    #[min = 1]
+    #[max = 10080]
+    reveal_window_minutes: Option<u32>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let guild_config = config.guild_mut(guild_id);
+
+    if let Some(moderator_role) = moderator_role {
+        guild_config.moderator_role = Some(moderator_role);
+    }
+
+    if let Some(reveal_window_minutes) = reveal_window_minutes {
+        guild_config.reveal_window_minutes = reveal_window_minutes;
+    }
+
+    let summary = format!(
+        "Moderator role: {}\nReveal approval window: {} minutes",
+        guild_config
+            .moderator_role
+            .map_or("not set".to_string(), |role| role.mention().to_string()),
+        guild_config.reveal_window_minutes
+    );
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the moderation settings but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(summary).await?;
+    Ok(())
+}
@@ -0,0 +1,82 @@
+use chrono::{Duration, Utc};
+
+use crate::{
+    Context, Error,
+    config::RetentionAction,
+    retention::{Purge, purge_in_background},
+};
+
+/// Delete or redact this server's entries in the audit log.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    rename = "purge-audit",
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Delete or redact this server's audit log entries, optionally only those older than N days."
+    )
+)]
+pub async fn purge_audit(
+    ctx: Context<'_>,
+    #[description = "Only purge entries older than this many days (all entries if not set)."]
+    #[min = 1]
+    #[max = 3650]
+    older_than_days: Option<u32>,
+    #[description = "Whether to delete the entries or only remove their author hash and content."]
+    action: Option<RetentionAction>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let directory = ctx.data().config.read().await.logging.directory.clone();
+    let result = purge_in_background(
+        directory,
+        ctx.data().storage.clone(),
+        Purge {
+            guild_id: Some(guild_id),
+            before: older_than_days.map(|days| Utc::now() - Duration::days(days.into())),
+            action: action.unwrap_or_default(),
+        },
+    )
+    .await;
+
+    let stats = match result {
+        Ok(stats) => stats,
+        Err(e) => {
+            log::error!(
+                "Failed to purge the audit entries of guild {}: {:?}",
+                guild_id,
+                e
+            );
+            ctx.say("An error occurred while purging the audit log. Please try again later.")
+                .await?;
+            return Ok(());
+        }
+    };
+
+    log::warn!(
+        "{} purged the audit entries of guild {}: {} deleted, {} redacted",
+        ctx.author().id,
+        guild_id,
+        stats.deleted,
+        stats.redacted
+    );
+
+    let scope = match older_than_days {
+        Some(days) => format!("this server's audit entries older than {} days", days),
+        None => "this server's audit entries".to_string(),
+    };
+    ctx.say(match stats.deleted + stats.redacted {
+        0 => format!("There were none of {} to purge.", scope),
+        _ => format!(
+            "Purged {}: {} deleted, {} redacted.",
+            scope, stats.deleted, stats.redacted
+        ),
+    })
+    .await?;
+
+    Ok(())
+}
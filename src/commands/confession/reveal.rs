@@ -0,0 +1,315 @@
+use std::pin::pin;
+
+use chrono::{Duration, Utc};
+use poise::{
+    CreateReply,
+    serenity_prelude::{
+        self as serenity, CacheHttp, ComponentInteraction, CreateActionRow, CreateButton,
+        CreateEmbed, CreateInteractionResponse, CreateInteractionResponseFollowup,
+        CreateInteractionResponseMessage, CreateMessage, EditMessage, GuildId, Mentionable, UserId,
+        futures::StreamExt,
+    },
+};
+use serenity::{Builder, ButtonStyle, Color};
+
+use super::{ensure_moderator, is_moderator};
+use crate::{
+    Context, Data, Error, sanitize::no_mentions, storage::RevealApprovalError,
+    utils::REVEAL_BUTTON_PREFIX,
+};
+
+/// Ask to reveal the author of a confession. A second moderator has to approve the request.
+#[poise::command(
+    slash_command,
+    guild_only,
+    description_localized(
+        "en-US",
+        "Ask to reveal the author of a confession (a second moderator has to approve)."
+    )
+)]
+pub async fn reveal(
+    ctx: Context<'_>,
+    #[description = "The number of the confession."]
+    #[min = 1]
+    number: i64,
+) -> Result<(), Error> {
+    if !ensure_moderator(ctx).await? {
+        return Ok(());
+    }
+
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+    let data = ctx.data();
+
+    let Some(confession) = data.storage.get_confession(guild_id, number)? else {
+        ctx.send(
+            CreateReply::default()
+                .content(format!("Confession #{} does not exist.", number))
+                .ephemeral(true),
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let window = data
+        .config
+        .read()
+        .await
+        .guild(guild_id)
+        .reveal_window_minutes;
+    let expires_at = Utc::now() + Duration::minutes(window.into());
+
+    let request_id =
+        data.storage
+            .create_reveal_request(guild_id, number, ctx.author().id, expires_at)?;
+    data.storage.append_moderation_audit(
+        guild_id,
+        "reveal_requested",
+        ctx.author().id,
+        Some(number),
+        &format!("reveal request {}", request_id),
+    )?;
+
+    let mut embed = CreateEmbed::new()
+        .title(format!("Reveal request for confession #{}", number))
+        .description(&confession.preview)
+        .field("Requested by", ctx.author().mention().to_string(), true)
+        .field("Expires", format!("<t:{}:R>", expires_at.timestamp()), true)
+        .color(Color::from_rgb(255, 165, 0))
+        .timestamp(confession.created_at);
+
+    if let Some(thread_id) = confession.thread_id {
+        embed = embed.field("Thread", thread_id.mention().to_string(), true);
+    }
+
+    ctx.send(
+        CreateReply::default()
+            .content(
+                "A second moderator has to approve this request before the author is revealed.",
+            )
+            .embed(embed)
+            .components(vec![CreateActionRow::Buttons(vec![
+                CreateButton::new(format!("{}:{}", REVEAL_BUTTON_PREFIX, request_id))
+                    .label("Approve reveal")
+                    .style(ButtonStyle::Danger),
+            ])]),
+    )
+    .await?;
+
+    Ok(())
+}
+
+/// Returns whether a component custom id belongs to a reveal approval button.
+pub fn is_reveal_component(custom_id: &str) -> bool {
+    custom_id.starts_with(REVEAL_BUTTON_PREFIX)
+}
+
+async fn respond_ephemeral(
+    ctx: &serenity::Context,
+    component: &ComponentInteraction,
+    content: &str,
+) -> Result<(), Error> {
+    component
+        .create_response(
+            ctx.http(),
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .allowed_mentions(no_mentions())
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+/// Handles a moderator pressing "Approve reveal" on a reveal request.
+pub async fn handle_reveal_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    component: &ComponentInteraction,
+) -> Result<(), Error> {
+    let Some(request_id) = component
+        .data
+        .custom_id
+        .strip_prefix(REVEAL_BUTTON_PREFIX)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(|id| id.parse::<i64>().ok())
+    else {
+        return Ok(());
+    };
+
+    let Some(guild_id) = component.guild_id else {
+        return Ok(());
+    };
+
+    let moderator_role = data.config.read().await.guild(guild_id).moderator_role;
+
+    if !is_moderator(component.member.as_ref(), moderator_role) {
+        return respond_ephemeral(
+            ctx,
+            component,
+            "You need the moderator role to approve reveal requests.",
+        )
+        .await;
+    }
+
+    let request = match data
+        .storage
+        .approve_reveal_request(request_id, component.user.id)?
+    {
+        Ok(request) => request,
+        Err(RevealApprovalError::SameModerator) => {
+            return respond_ephemeral(
+                ctx,
+                component,
+                "A different moderator has to approve your reveal request.",
+            )
+            .await;
+        }
+        Err(RevealApprovalError::Expired) => {
+            component
+                .create_response(
+                    ctx.http(),
+                    CreateInteractionResponse::UpdateMessage(
+                        CreateInteractionResponseMessage::new()
+                            .allowed_mentions(no_mentions())
+                            .content("This reveal request expired before it was approved.")
+                            .components(vec![]),
+                    ),
+                )
+                .await?;
+            return Ok(());
+        }
+        Err(RevealApprovalError::NotFound | RevealApprovalError::AlreadyHandled) => {
+            return respond_ephemeral(
+                ctx,
+                component,
+                "This reveal request has already been handled.",
+            )
+            .await;
+        }
+    };
+
+    data.storage.append_moderation_audit(
+        guild_id,
+        "reveal_approved",
+        component.user.id,
+        Some(request.confession_number),
+        &format!(
+            "reveal request {} requested by {}",
+            request_id, request.requested_by
+        ),
+    )?;
+
+    // Going through the member list can take a while
+    component.defer_ephemeral(ctx.http()).await?;
+
+    let author = match data
+        .storage
+        .get_confession(request.guild_id, request.confession_number)?
+    {
+        Some(confession) => {
+            resolve_author(ctx, data, request.guild_id, &confession.author_hash).await?
+        }
+        None => None,
+    };
+
+    data.storage.append_moderation_audit(
+        guild_id,
+        "author_revealed",
+        component.user.id,
+        Some(request.confession_number),
+        &format!(
+            "reveal request {} disclosed to {} and {}, author {}",
+            request_id,
+            request.requested_by,
+            component.user.id,
+            if author.is_some() {
+                "found"
+            } else {
+                "not found"
+            }
+        ),
+    )?;
+
+    let result = match author {
+        Some(author) => format!(
+            "Confession #{} was written by {} ({}).",
+            request.confession_number,
+            author.mention(),
+            author
+        ),
+        None => format!(
+            "The author of confession #{} could not be matched to a current member. They may have left the guild, or the hash secret was rotated beyond the lookup window.",
+            request.confession_number
+        ),
+    };
+
+    CreateInteractionResponseFollowup::new()
+        .allowed_mentions(no_mentions())
+        .content(&result)
+        .ephemeral(true)
+        .execute(ctx.http(), (None, &component.token))
+        .await?;
+
+    // The requester gets the result privately as well
+    if let Err(e) = request
+        .requested_by
+        .direct_message(
+            ctx.http(),
+            CreateMessage::new()
+                .allowed_mentions(no_mentions())
+                .content(&result),
+        )
+        .await
+    {
+        log::warn!(
+            "Failed to send reveal result to moderator {}: {:?}",
+            request.requested_by,
+            e
+        );
+    }
+
+    component
+        .channel_id
+        .edit_message(
+            ctx.http(),
+            component.message.id,
+            EditMessage::new()
+                .allowed_mentions(no_mentions())
+                .content(format!(
+                    "Approved by {}. The result has been sent to {} and {}.",
+                    component.user.mention(),
+                    request.requested_by.mention(),
+                    component.user.mention()
+                ))
+                .components(vec![]),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Finds the guild member whose author hash matches, by hashing every member's id.
+async fn resolve_author(
+    ctx: &serenity::Context,
+    data: &Data,
+    guild_id: GuildId,
+    author_hash: &str,
+) -> Result<Option<UserId>, Error> {
+    // Don't hold the configuration lock while paging through the member list
+    let config = data.config.read().await.clone();
+    let mut members = pin!(guild_id.members_iter(ctx.http()));
+
+    while let Some(member) = members.next().await {
+        let member = member?;
+
+        if config.is_author_hash_of(author_hash, guild_id, member.user.id) {
+            return Ok(Some(member.user.id));
+        }
+    }
+
+    Ok(None)
+}
@@ -0,0 +1,131 @@
+use chrono::NaiveTime;
+use poise::ChoiceParameter;
+
+use crate::{
+    Context, Error,
+    config::{ScheduleMode, ScheduleSettings},
+};
+
+fn describe(settings: &ScheduleSettings) -> String {
+    let batch_times = if settings.batch_times.is_empty() {
+        "none".to_string()
+    } else {
+        settings
+            .batch_times
+            .iter()
+            .map(|time| time.format("%H:%M").to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    format!(
+        "Posting: {}\nRandom delay: {} to {} minutes\nBatch times (UTC): {}",
+        settings.mode.name(),
+        settings.min_delay_minutes,
+        settings.max_delay_minutes,
+        batch_times
+    )
+}
+
+/// Parses a comma-separated list of times such as `09:00, 18:30`, sorted and without duplicates.
+fn parse_batch_times(input: &str) -> Option<Vec<NaiveTime>> {
+    let mut times = input
+        .split(',')
+        .map(|time| NaiveTime::parse_from_str(time.trim(), "%H:%M").ok())
+        .collect::<Option<Vec<_>>>()?;
+
+    times.sort();
+    times.dedup();
+    Some(times)
+}
+
+/// Configure when confessions are posted after they are submitted.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Delay confessions by a random amount or post them in batches, so timing can't give authors away."
+    )
+)]
+pub async fn schedule(
+    ctx: Context<'_>,
+    #[description = "When confessions are posted."] mode: Option<ScheduleMode>,
+    #[description = "Shortest random delay, in minutes (also the least wait before a batch)."]
+    #[max = 1440]
+    min_delay_minutes: Option<u32>,
+    #[description = "Longest random delay, in minutes."]
+    #[max = 1440]
+    max_delay_minutes: Option<u32>,
+    #[description = "Batch times in UTC, e.g. 09:00, 18:30 (\"none\" to clear)."]
+    batch_times: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    let batch_times = match batch_times.as_deref().map(str::trim) {
+        None => None,
+        Some(input) if input.eq_ignore_ascii_case("none") => Some(Vec::new()),
+        Some(input) => match parse_batch_times(input) {
+            Some(times) => Some(times),
+            None => {
+                ctx.say("Error: Batch times must look like `09:00, 18:30` (24-hour clock, UTC).")
+                    .await?;
+                return Ok(());
+            }
+        },
+    };
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let settings = &mut config.guild_mut(guild_id).schedule;
+    let mut updated = settings.clone();
+
+    if let Some(mode) = mode {
+        updated.mode = mode;
+    }
+
+    if let Some(min_delay_minutes) = min_delay_minutes {
+        updated.min_delay_minutes = min_delay_minutes;
+    }
+
+    if let Some(max_delay_minutes) = max_delay_minutes {
+        updated.max_delay_minutes = max_delay_minutes;
+    }
+
+    if let Some(batch_times) = batch_times {
+        updated.batch_times = batch_times;
+    }
+
+    if updated.min_delay_minutes > updated.max_delay_minutes {
+        ctx.say("Error: The shortest delay can't be longer than the longest delay.")
+            .await?;
+        return Ok(());
+    }
+
+    if updated.mode == ScheduleMode::Batches && updated.batch_times.is_empty() {
+        ctx.say("Error: Posting in batches needs at least one batch time, e.g. `batch_times:09:00, 18:30`.")
+            .await?;
+        return Ok(());
+    }
+
+    let summary = describe(&updated);
+    *settings = updated;
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the posting schedule but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(summary).await?;
+    Ok(())
+}
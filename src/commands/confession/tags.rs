@@ -0,0 +1,204 @@
+use poise::serenity_prelude::{self as serenity, AutocompleteChoice, ForumTag, ForumTagId};
+
+use crate::{
+    Context, Error,
+    commands::board::{autocomplete_board, board_argument, board_name},
+    config::ForumTags,
+};
+
+// Value that clears the default or content warning tag
+const CLEAR: &str = "none";
+
+/// Fetches the tags of the board's channel, or `None` if it isn't a Forum channel.
+async fn forum_tags_of_board(ctx: Context<'_>, board: Option<&str>) -> Option<Vec<ForumTag>> {
+    let guild_id = ctx.guild_id()?;
+    let channel_id = ctx
+        .data()
+        .config
+        .read()
+        .await
+        .board(guild_id, board)?
+        .channel;
+
+    match channel_id.to_channel(ctx).await {
+        Ok(serenity::Channel::Guild(channel)) if channel.kind == serenity::ChannelType::Forum => {
+            Some(channel.available_tags)
+        }
+        Ok(_) => None,
+        Err(e) => {
+            log::error!("Failed to fetch channel {}: {:?}", channel_id, e);
+            None
+        }
+    }
+}
+
+/// Offers the tags of the confession forum.
+async fn autocomplete_tag(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice> {
+    let partial = partial.to_lowercase();
+
+    forum_tags_of_board(ctx, board_argument(ctx).as_deref())
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|tag| tag.name.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|tag| AutocompleteChoice::new(tag.name, tag.id.to_string()))
+        .collect()
+}
+
+/// Looks up a tag given by id (as sent by the autocomplete) or by name.
+fn find_tag(tags: &[ForumTag], input: &str) -> Result<ForumTagId, String> {
+    tags.iter()
+        .find(|tag| tag.id.to_string() == input || tag.name.eq_ignore_ascii_case(input.trim()))
+        .map(|tag| tag.id)
+        .ok_or_else(|| format!("Error: The forum has no tag called `{}`.", input))
+}
+
+fn describe(settings: &ForumTags, tags: &[ForumTag]) -> String {
+    let name = |id: ForumTagId| {
+        tags.iter().find(|tag| tag.id == id).map_or_else(
+            || format!("unknown tag {}", id),
+            |tag| format!("`{}`", tag.name),
+        )
+    };
+
+    format!(
+        "Default tag: {}\nContent warning tag: {}\nCategories: {}",
+        settings.default_tag.map_or("none".to_string(), name),
+        settings
+            .content_warning_tag
+            .map_or("none".to_string(), name),
+        if settings.categories.is_empty() {
+            "none".to_string()
+        } else {
+            settings
+                .categories
+                .iter()
+                .map(|id| name(*id))
+                .collect::<Vec<_>>()
+                .join(", ")
+        }
+    )
+}
+
+/// Choose the forum tags applied to confession posts.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Choose the forum tags applied to confession posts and the categories authors pick from."
+    )
+)]
+pub async fn tags(
+    ctx: Context<'_>,
+    #[description = "Tag added to every confession post (\"none\" to remove)."]
+    #[autocomplete = "autocomplete_tag"]
+    default_tag: Option<String>,
+    #[description = "Tag authors can choose as the category of their confession."]
+    #[autocomplete = "autocomplete_tag"]
+    add_category: Option<String>,
+    #[description = "Category authors should no longer be offered."]
+    #[autocomplete = "autocomplete_tag"]
+    remove_category: Option<String>,
+    #[description = "Tag added to confession posts with a content warning (\"none\" to remove)."]
+    #[autocomplete = "autocomplete_tag"]
+    content_warning_tag: Option<String>,
+    #[description = "Board to configure (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+    let board = board.as_deref().map(board_name);
+
+    let Some(available) = forum_tags_of_board(ctx, board.as_deref()).await else {
+        ctx.say(
+            "Error: Forum tags need a Forum channel. Set one with `/set_confession_thread` or `/confession board set` first.",
+        )
+        .await?;
+        return Ok(());
+    };
+
+    let resolve = |input: Option<&str>| input.map(|input| find_tag(&available, input)).transpose();
+    let resolve_clearable = |input: Option<&str>| match input {
+        Some(CLEAR) => Ok(Some(None)),
+        input => resolve(input).map(|tag| tag.map(Some)),
+    };
+
+    let resolved = (
+        resolve_clearable(default_tag.as_deref()),
+        resolve(add_category.as_deref()),
+        resolve(remove_category.as_deref()),
+        resolve_clearable(content_warning_tag.as_deref()),
+    );
+
+    let (default_tag, add_category, remove_category, content_warning_tag) = match resolved {
+        (Ok(default_tag), Ok(add_category), Ok(remove_category), Ok(content_warning_tag)) => (
+            default_tag,
+            add_category,
+            remove_category,
+            content_warning_tag,
+        ),
+        (Err(e), _, _, _) | (_, Err(e), _, _) | (_, _, Err(e), _) | (_, _, _, Err(e)) => {
+            ctx.say(e).await?;
+            return Ok(());
+        }
+    };
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let Some(board_settings) = config
+        .guild_mut(guild_id)
+        .board_settings_mut(board.as_deref())
+    else {
+        ctx.say(format!(
+            "There is no board called `{}`.",
+            board.unwrap_or_default()
+        ))
+        .await?;
+        return Ok(());
+    };
+    let settings = &mut board_settings.forum_tags;
+
+    if let Some(default_tag) = default_tag {
+        settings.default_tag = default_tag;
+    }
+
+    if let Some(content_warning_tag) = content_warning_tag {
+        settings.content_warning_tag = content_warning_tag;
+    }
+
+    if let Some(tag) = add_category
+        && !settings.categories.contains(&tag)
+    {
+        settings.categories.push(tag);
+    }
+
+    if let Some(tag) = remove_category {
+        settings.categories.retain(|category| *category != tag);
+    }
+
+    // Tags deleted from the forum since they were configured are dropped as well
+    let available_ids: Vec<_> = available.iter().map(|tag| tag.id).collect();
+    settings.retain_available(&available_ids);
+
+    let summary = describe(settings, &available);
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the forum tags but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(summary).await?;
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use poise::ChoiceParameter;
+
+use crate::{
+    Context, Error,
+    commands::board::{autocomplete_board, board_name},
+    config::{ArchiveAfter, PostMode, ThreadSettings},
+};
+
+fn describe(settings: &ThreadSettings) -> String {
+    format!(
+        "Confessions in Text channels: {}\nArchive after: {}\nLocked: {}\nSlowmode: {}",
+        settings.mode.name(),
+        settings.archive_after.name(),
+        if settings.locked { "yes" } else { "no" },
+        match settings.slowmode_seconds {
+            0 => "off".to_string(),
+            seconds => format!("{} seconds", seconds),
+        }
+    )
+}
+
+/// Configure the threads and forum posts created for confessions.
+#[poise::command(
+    slash_command,
+    guild_only,
+    ephemeral,
+    required_permissions = "MANAGE_GUILD",
+    description_localized(
+        "en-US",
+        "Configure the threads and forum posts created for confessions."
+    )
+)]
+pub async fn threads(
+    ctx: Context<'_>,
+    #[description = "Whether confessions in a Text channel get their own thread."] mode: Option<
+        PostMode,
+    >,
+    #[description = "Inactivity after which threads are archived."] archive_after: Option<
+        ArchiveAfter,
+    >,
+    #[description = "Whether threads are read-only (no anonymous replies either)."] locked: Option<
+        bool,
+    >,
+    #[description = "Seconds members wait between messages in a thread (0 turns it off)."]
+    #[max = 21600]
+    slowmode_seconds: Option<u16>,
+    #[description = "Board to configure (the default board when omitted)."]
+    #[autocomplete = "autocomplete_board"]
+    board: Option<String>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+    let board = board.as_deref().map(board_name);
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    let Some(board_settings) = config
+        .guild_mut(guild_id)
+        .board_settings_mut(board.as_deref())
+    else {
+        ctx.say(format!(
+            "There is no board called `{}`.",
+            board.unwrap_or_default()
+        ))
+        .await?;
+        return Ok(());
+    };
+    let settings = &mut board_settings.threads;
+
+    if let Some(mode) = mode {
+        settings.mode = mode;
+    }
+
+    if let Some(archive_after) = archive_after {
+        settings.archive_after = archive_after;
+    }
+
+    if let Some(locked) = locked {
+        settings.locked = locked;
+    }
+
+    if let Some(slowmode_seconds) = slowmode_seconds {
+        settings.slowmode_seconds = slowmode_seconds;
+    }
+
+    let summary = describe(settings);
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the thread settings but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    ctx.say(summary).await?;
+    Ok(())
+}
@@ -1,3 +1,10 @@
+pub mod board;
+pub mod category;
 pub mod confess;
 pub mod confessembed;
-pub mod set_confession_thread;
\ No newline at end of file
+pub mod confession;
+pub mod preview;
+pub mod reply;
+pub mod review;
+pub mod rotate_author_secret;
+pub mod set_confession_thread;
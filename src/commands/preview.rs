@@ -0,0 +1,167 @@
+use std::time::Duration;
+
+use chrono::Utc;
+use poise::serenity_prelude::{
+    self as serenity, Attachment, ButtonStyle, ComponentInteraction, CreateActionRow, CreateButton,
+    CreateInteractionResponseMessage, GuildId,
+};
+
+use crate::{
+    Data, filter,
+    sanitize::{no_mentions, sanitize_mentions},
+    storage::Draft,
+    utils::PREVIEW_BUTTON_PREFIX,
+};
+
+/// What the author decided to do with the preview of their confession.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PreviewChoice {
+    Submit,
+    Edit,
+    Cancel,
+}
+
+impl PreviewChoice {
+    fn as_str(self) -> &'static str {
+        match self {
+            PreviewChoice::Submit => "submit",
+            PreviewChoice::Edit => "edit",
+            PreviewChoice::Cancel => "cancel",
+        }
+    }
+
+    fn parse(choice: &str) -> Option<Self> {
+        match choice {
+            "submit" => Some(PreviewChoice::Submit),
+            "edit" => Some(PreviewChoice::Edit),
+            "cancel" => Some(PreviewChoice::Cancel),
+            _ => None,
+        }
+    }
+
+    fn custom_id(self, key: &str) -> String {
+        format!("{}:{}:{}", PREVIEW_BUTTON_PREFIX, self.as_str(), key)
+    }
+}
+
+fn preview_buttons(key: &str) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(PreviewChoice::Submit.custom_id(key))
+            .label("Submit")
+            .style(ButtonStyle::Success),
+        CreateButton::new(PreviewChoice::Edit.custom_id(key))
+            .label("Edit")
+            .style(ButtonStyle::Secondary),
+        CreateButton::new(PreviewChoice::Cancel.custom_id(key))
+            .label("Cancel")
+            .style(ButtonStyle::Danger),
+    ])]
+}
+
+/// Builds the preview of a confession: its first embed as it would be posted on its board,
+/// after the board's content filter and mention handling, with Submit, Edit and Cancel buttons
+/// identified by `key`. On failure, the error is a message that can be shown to the user.
+pub async fn confession_preview(
+    data: &Data,
+    guild_id: GuildId,
+    draft: &Draft,
+    image: Option<&Attachment>,
+    key: &str,
+) -> Result<CreateInteractionResponseMessage, String> {
+    let Some(board) = data
+        .config
+        .read()
+        .await
+        .board(guild_id, draft.board.as_deref())
+    else {
+        return Err("The confession board no longer exists.".to_string());
+    };
+
+    let filter_config = &board.settings.filter;
+    let posted = |text: &str| {
+        Some(sanitize_mentions(
+            &filter::apply(filter_config, text).content,
+            filter_config.mention_mode,
+        ))
+        .filter(|text| !text.is_empty())
+    };
+
+    let content = posted(&draft.content).unwrap_or_default();
+    let title = draft.title.as_deref().and_then(posted);
+    let content_warning = draft.content_warning.as_deref().and_then(posted);
+    let signature = draft.signature.as_deref().and_then(posted);
+
+    let number = data
+        .storage
+        .next_confession_number(guild_id)
+        .unwrap_or_else(|e| {
+            log::error!("Failed to look up the next confession number: {:?}", e);
+            1
+        });
+
+    let embeds = board.settings.appearance.confession_embeds(
+        number,
+        Utc::now(),
+        &content,
+        title.as_deref(),
+        content_warning.as_deref(),
+        signature.as_deref(),
+    );
+    let parts = embeds.len();
+    let mut embed = embeds
+        .into_iter()
+        .next()
+        .expect("A confession has at least one embed");
+
+    let mut notes = vec![
+        "This is how your confession will look. Its number may change if other confessions are posted first.".to_string(),
+    ];
+
+    if parts > 1 {
+        notes.push(format!(
+            "It is posted in {} parts; only the first one is shown here.",
+            parts
+        ));
+    }
+
+    // The original upload is only shown to its author; the posted copy has no metadata
+    match image {
+        Some(image) if content_warning.is_none() => embed = embed.image(&image.url),
+        Some(_) => {
+            notes.push("Your image is posted as a spoiler below the confession.".to_string())
+        }
+        None => {}
+    }
+
+    Ok(CreateInteractionResponseMessage::new()
+        .allowed_mentions(no_mentions())
+        .content(notes.join("\n"))
+        .embed(embed)
+        .components(preview_buttons(key)))
+}
+
+/// Waits for the author to press one of the preview's buttons.
+pub async fn await_preview_choice(
+    ctx: &serenity::Context,
+    key: String,
+) -> Option<(ComponentInteraction, PreviewChoice)> {
+    let prefix = format!("{}:", PREVIEW_BUTTON_PREFIX);
+    let suffix = format!(":{}", key);
+
+    let interaction = serenity::collector::ComponentInteractionCollector::new(&ctx.shard)
+        .filter(move |interaction| {
+            interaction.data.custom_id.starts_with(&prefix)
+                && interaction.data.custom_id.ends_with(&suffix)
+        })
+        .timeout(Duration::from_secs(600))
+        .await?;
+
+    let choice = interaction
+        .data
+        .custom_id
+        .split(':')
+        .nth(1)
+        .and_then(PreviewChoice::parse)?;
+
+    Some((interaction, choice))
+}
@@ -0,0 +1,290 @@
+use std::time::Duration;
+
+use poise::{
+    Modal,
+    serenity_prelude::{
+        self as serenity, CacheHttp, ComponentInteraction, CreateButton, CreateEmbed,
+        CreateEmbedFooter, CreateInteractionResponse, CreateInteractionResponseMessage,
+        CreateMessage, GuildId, User,
+    },
+};
+use serenity::{ButtonStyle, Color};
+
+use crate::{
+    Data, Error,
+    commands::confession::ban::ban_notice,
+    filter::{self, Verdict},
+    logging::{AuditEntry, AuditOutcome},
+    sanitize::{no_mentions, sanitize_mentions},
+    utils::{REPLY_BUTTON_PREFIX, ReplyModal},
+};
+
+/// The "Reply anonymously" button posted with every confession.
+pub fn reply_button(confession_number: i64) -> CreateButton {
+    CreateButton::new(format!("{}:{}", REPLY_BUTTON_PREFIX, confession_number))
+        .label("Reply anonymously")
+        .style(ButtonStyle::Secondary)
+}
+
+/// Returns whether a component custom id belongs to a "Reply anonymously" button.
+pub fn is_reply_component(custom_id: &str) -> bool {
+    custom_id.starts_with(REPLY_BUTTON_PREFIX)
+}
+
+/// Handles the "Reply anonymously" button: asks for the reply and posts it in the confession's thread.
+pub async fn handle_reply_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    component: &ComponentInteraction,
+) -> Result<(), Error> {
+    let Some(confession_number) = component
+        .data
+        .custom_id
+        .strip_prefix(REPLY_BUTTON_PREFIX)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(|number| number.parse::<i64>().ok())
+    else {
+        return Ok(());
+    };
+
+    let guild_id = component
+        .guild_id
+        .expect("Reply button should only be sent in guilds");
+
+    // Turn banned users away before they spend time writing a reply
+    if let Some(notice) = ban_notice(data, guild_id, component.user.id).await? {
+        component
+            .create_response(
+                ctx.http(),
+                CreateInteractionResponse::Message(
+                    CreateInteractionResponseMessage::new()
+                        .allowed_mentions(no_mentions())
+                        .content(notice)
+                        .ephemeral(true),
+                ),
+            )
+            .await?;
+        return Ok(());
+    }
+
+    let custom_id = component.id.to_string();
+    component
+        .create_response(ctx.http(), ReplyModal::create(None, custom_id.clone()))
+        .await?;
+
+    let response = serenity::collector::ModalInteractionCollector::new(&ctx.shard)
+        .filter(move |modal_interaction| modal_interaction.data.custom_id == custom_id)
+        .timeout(Duration::from_secs(3600))
+        .await;
+
+    let Some(modal_interaction) = response else {
+        return Ok(());
+    };
+
+    let Ok(modal) = ReplyModal::parse(modal_interaction.data.clone()) else {
+        return Ok(());
+    };
+
+    let reply = send_reply(
+        guild_id,
+        &component.user,
+        data,
+        ctx.http(),
+        confession_number,
+        modal.content.trim().to_string(),
+    )
+    .await;
+
+    modal_interaction
+        .create_response(
+            ctx.http(),
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .allowed_mentions(no_mentions())
+                    .content(reply)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Runs a reply through the same checks as a confession and posts it in the confession's thread.
+/// Returns the message to show to the author.
+async fn send_reply(
+    guild_id: GuildId,
+    author: &User,
+    data: &Data,
+    cache: &serenity::Http,
+    confession_number: i64,
+    reply_content: String,
+) -> String {
+    // The ban may have been issued while the modal was open
+    match ban_notice(data, guild_id, author.id).await {
+        Ok(Some(notice)) => return notice,
+        Ok(None) => {}
+        Err(e) => {
+            log::error!("Failed to check bans for guild {}: {:?}", guild_id, e);
+            return "An error occurred while posting your reply. Please try again later."
+                .to_string();
+        }
+    }
+
+    // 1. Log the reply for auditing, using the same keyed hash as for confessions
+    let (hash, author_hashes, guild_config, audit_key) = {
+        let config = data.config.read().await;
+        (
+            config.author_hash(guild_id, author.id),
+            config.author_hashes(guild_id, author.id),
+            config.guild(guild_id),
+            config.audit_key(),
+        )
+    };
+    AuditEntry::reply(
+        guild_id,
+        confession_number,
+        &hash,
+        &reply_content,
+        AuditOutcome::Received,
+    )
+    .log(audit_key.as_ref());
+
+    let confession = match data.storage.get_confession(guild_id, confession_number) {
+        Ok(Some(confession)) if !confession.deleted => confession,
+        Ok(_) => return "This confession no longer exists.".to_string(),
+        Err(e) => {
+            log::error!(
+                "Failed to load confession #{} of guild {}: {:?}",
+                confession_number,
+                guild_id,
+                e
+            );
+            return "An error occurred while posting your reply. Please try again later."
+                .to_string();
+        }
+    };
+
+    let Some(thread_id) = confession.thread_id else {
+        return "This confession has no thread to reply in.".to_string();
+    };
+
+    // 2. Run the reply through the content filter of the confession's board (or the default
+    // board's if it has been deleted). Replies can't wait in the review queue or carry a content
+    // warning, so anything the filter would hold or only allow with a warning is refused.
+    let filter_config = &guild_config
+        .board_settings(confession.board.as_deref())
+        .unwrap_or(&guild_config.settings)
+        .filter;
+    let outcome = filter::apply(filter_config, &reply_content);
+    match outcome.verdict {
+        Verdict::Allow => {}
+        Verdict::Hold(reason) | Verdict::Reject(reason) => {
+            AuditEntry::reply(
+                guild_id,
+                confession_number,
+                &hash,
+                &reply_content,
+                AuditOutcome::Filtered,
+            )
+            .log(audit_key.as_ref());
+            return format!(
+                "Your reply was not posted because it contains {}, which this server does not allow.",
+                reason
+            );
+        }
+    }
+
+    if let Some(reason) = outcome.needs_warning {
+        AuditEntry::reply(
+            guild_id,
+            confession_number,
+            &hash,
+            &reply_content,
+            AuditOutcome::Filtered,
+        )
+        .log(audit_key.as_ref());
+        return format!(
+            "Your reply was not posted because it contains {}, which this server only allows in confessions with a content warning.",
+            reason
+        );
+    }
+
+    let reply_content = sanitize_mentions(&outcome.content, filter_config.mention_mode);
+    if reply_content.is_empty() {
+        return "Your reply is empty after applying this server's content filter.".to_string();
+    }
+
+    // 3. Label the reply: the confession's author is OP, everyone else gets a number that
+    // stays the same for all their replies in this thread
+    let (label, color) = if author_hashes.contains(&confession.author_hash) {
+        ("OP".to_string(), Color::from_rgb(255, 165, 0))
+    } else {
+        match data
+            .storage
+            .reply_alias(guild_id, confession_number, &author_hashes)
+        {
+            Ok(alias) => (format!("Anonymous #{}", alias), Color::BLURPLE),
+            Err(e) => {
+                log::error!(
+                    "Failed to assign reply alias for confession #{} of guild {}: {:?}",
+                    confession_number,
+                    guild_id,
+                    e
+                );
+                return "An error occurred while posting your reply. Please try again later."
+                    .to_string();
+            }
+        }
+    };
+
+    let embed = CreateEmbed::new()
+        .title(label)
+        .description(&reply_content)
+        .color(color)
+        .footer(CreateEmbedFooter::new(format!(
+            "Anonymous reply to confession #{}",
+            confession_number
+        )));
+
+    // 4. Post the reply in the confession's thread
+    let message = match thread_id
+        .send_message(
+            cache,
+            CreateMessage::new()
+                .embed(embed)
+                .allowed_mentions(no_mentions()),
+        )
+        .await
+    {
+        Ok(message) => message,
+        Err(e) => {
+            log::error!("Failed to send reply in thread {}: {:?}", thread_id, e);
+            return "An error occurred while posting your reply. The thread may be locked or deleted.".to_string();
+        }
+    };
+
+    AuditEntry::reply(
+        guild_id,
+        confession_number,
+        &hash,
+        &reply_content,
+        AuditOutcome::Published,
+    )
+    .posted_in(confession.channel_id, Some(thread_id))
+    .log(audit_key.as_ref());
+
+    if let Err(e) =
+        data.storage
+            .insert_reply(guild_id, confession_number, &hash, thread_id, message.id)
+    {
+        log::error!(
+            "Failed to store reply to confession #{} of guild {}: {:?}",
+            confession_number,
+            guild_id,
+            e
+        );
+    }
+
+    "Your anonymous reply has been posted.".to_string()
+}
@@ -0,0 +1,510 @@
+use std::time::Duration;
+
+use poise::serenity_prelude::{
+    self as serenity, Builder, CacheHttp, ChannelId, ComponentInteraction, CreateActionRow,
+    CreateButton, CreateEmbed, CreateEmbedFooter, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, CreateMessage,
+    EditInteractionResponse, GuildId, Mentionable,
+};
+use serenity::{ButtonStyle, Color};
+
+use crate::{
+    Context, Data, Error,
+    appearance::PART_MAX_CHARS,
+    commands::{
+        confess::{Posting, post_or_schedule},
+        confession::is_moderator,
+    },
+    logging::{AuditEntry, AuditOutcome},
+    sanitize::no_mentions,
+    storage::Draft,
+    utils::{ConfessionModal, REVIEW_BUTTON_PREFIX, truncate},
+};
+
+/// What a moderator decided to do with a pending confession.
+#[derive(Debug, Clone, Copy)]
+enum ReviewAction {
+    Approve,
+    Reject,
+    Edit,
+}
+
+impl ReviewAction {
+    fn as_str(self) -> &'static str {
+        match self {
+            ReviewAction::Approve => "approve",
+            ReviewAction::Reject => "reject",
+            ReviewAction::Edit => "edit",
+        }
+    }
+
+    fn parse(action: &str) -> Option<Self> {
+        match action {
+            "approve" => Some(ReviewAction::Approve),
+            "reject" => Some(ReviewAction::Reject),
+            "edit" => Some(ReviewAction::Edit),
+            _ => None,
+        }
+    }
+
+    fn custom_id(self, pending_id: i64) -> String {
+        format!("{}:{}:{}", REVIEW_BUTTON_PREFIX, self.as_str(), pending_id)
+    }
+}
+
+/// Choose the private channel where confessions wait for moderator approval before being posted.
+#[poise::command(
+    slash_command,
+    guild_only,
+    required_permissions = "MANAGE_MESSAGES",
+    description_localized(
+        "en-US",
+        "Choose the private channel where confessions wait for moderator approval (leave empty to disable)."
+    )
+)]
+pub async fn set_review_channel(
+    ctx: Context<'_>,
+    #[description = "The private Text channel for the review queue. Leave empty to post confessions immediately."]
+    review_channel: Option<ChannelId>,
+) -> Result<(), Error> {
+    let guild_id = ctx
+        .guild_id()
+        .ok_or("This command must be run in a guild.")?;
+
+    if let Some(review_channel) = review_channel {
+        let channel = review_channel.to_channel(ctx.http()).await?;
+
+        if !matches!(
+            channel,
+            serenity::Channel::Guild(ref guild_channel) if guild_channel.kind == serenity::ChannelType::Text
+        ) {
+            ctx.say("Error: The review channel must be a Text channel.")
+                .await?;
+            return Ok(());
+        }
+    }
+
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    config.guild_mut(guild_id).review_channel = review_channel;
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Updated the review channel but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    match review_channel {
+        Some(review_channel) => {
+            ctx.say(format!(
+                "Review mode enabled. New confessions will wait for approval in {}.",
+                review_channel.mention()
+            ))
+            .await?
+        }
+        None => {
+            ctx.say("Review mode disabled. New confessions will be posted immediately.")
+                .await?
+        }
+    };
+
+    Ok(())
+}
+
+/// Builds the embed shown to moderators for a pending confession.
+fn review_embed(pending_id: i64, draft: &Draft, status: Option<(String, Color)>) -> CreateEmbed {
+    let (status, color) =
+        status.unwrap_or_else(|| ("Awaiting review".to_string(), Color::from_rgb(255, 165, 0)));
+
+    let mut embed = CreateEmbed::new()
+        .title("Confession Awaiting Review")
+        .description(truncate(&draft.content, PART_MAX_CHARS));
+
+    // Only the first part of a long confession fits into the embed
+    let length = draft.content.chars().count();
+    if length > PART_MAX_CHARS {
+        embed = embed.field(
+            "Length",
+            format!(
+                "{} characters, shortened here. Use Edit to read all of it.",
+                length
+            ),
+            false,
+        );
+    }
+
+    if let Some(title) = &draft.title {
+        embed = embed.field("Title", title, true);
+    }
+
+    if let Some(board) = &draft.board {
+        embed = embed.field("Board", board, true);
+    }
+
+    if let Some(content_warning) = &draft.content_warning {
+        embed = embed.field("Content warning", content_warning, true);
+    }
+
+    if let Some(signature) = &draft.signature {
+        embed = embed.field("Signature", signature, true);
+    }
+
+    if let Some(image) = &draft.image {
+        embed = embed.image(image.embed_url());
+    }
+
+    embed
+        .field("Status", status, false)
+        .color(color)
+        .footer(CreateEmbedFooter::new(format!(
+            "Pending confession #{}",
+            pending_id
+        )))
+}
+
+fn review_buttons(pending_id: i64) -> Vec<CreateActionRow> {
+    vec![CreateActionRow::Buttons(vec![
+        CreateButton::new(ReviewAction::Approve.custom_id(pending_id))
+            .label("Approve")
+            .style(ButtonStyle::Success),
+        CreateButton::new(ReviewAction::Reject.custom_id(pending_id))
+            .label("Reject")
+            .style(ButtonStyle::Danger),
+        CreateButton::new(ReviewAction::Edit.custom_id(pending_id))
+            .label("Edit")
+            .style(ButtonStyle::Secondary),
+    ])]
+}
+
+/// Stores a confession in the review queue and posts it to the guild's review channel.
+/// On failure, the error is a message that can be shown to the user.
+pub async fn submit_for_review(
+    cache: &serenity::Http,
+    data: &Data,
+    guild_id: GuildId,
+    author_hash: &str,
+    draft: &Draft,
+    interaction_token: &str,
+    held_reason: Option<&str>,
+) -> Result<(), String> {
+    let Some(review_channel) = data.config.read().await.guild(guild_id).review_channel else {
+        return Err("This server has no review channel. Please ask a staff member to use `/set_review_channel`.".to_string());
+    };
+
+    let pending_id =
+        match data
+            .storage
+            .insert_pending(guild_id, author_hash, draft, interaction_token)
+        {
+            Ok(id) => id,
+            Err(e) => {
+                log::error!("Failed to store pending confession: {:?}", e);
+                return Err(
+                    "An error occurred while submitting your confession. Please try again later."
+                        .to_string(),
+                );
+            }
+        };
+
+    let status = held_reason.map(|reason| {
+        (
+            format!("Awaiting review ({})", reason),
+            Color::from_rgb(255, 165, 0),
+        )
+    });
+
+    let mut message = CreateMessage::new()
+        .allowed_mentions(no_mentions())
+        .embed(review_embed(pending_id, draft, status))
+        .components(review_buttons(pending_id));
+
+    if let Some(image) = &draft.image {
+        message = message.add_file(image.upload(false));
+    }
+
+    if let Err(e) = review_channel.send_message(cache, message).await {
+        log::error!(
+            "Failed to post pending confession {} in review channel {}: {:?}",
+            pending_id,
+            review_channel,
+            e
+        );
+
+        if let Err(e) = data.storage.delete_pending(pending_id) {
+            log::error!(
+                "Failed to remove pending confession {}: {:?}",
+                pending_id,
+                e
+            );
+        }
+
+        return Err(
+            "An error occurred while submitting your confession. Please try again later."
+                .to_string(),
+        );
+    }
+
+    Ok(())
+}
+
+/// Returns whether a component custom id belongs to the review queue buttons.
+pub fn is_review_component(custom_id: &str) -> bool {
+    custom_id.starts_with(REVIEW_BUTTON_PREFIX)
+}
+
+/// Handles the Approve / Reject / Edit buttons on a pending confession.
+pub async fn handle_review_interaction(
+    ctx: &serenity::Context,
+    data: &Data,
+    component: &ComponentInteraction,
+) -> Result<(), Error> {
+    let Some((action, pending_id)) = component
+        .data
+        .custom_id
+        .strip_prefix(REVIEW_BUTTON_PREFIX)
+        .and_then(|rest| rest.strip_prefix(':'))
+        .and_then(|rest| rest.split_once(':'))
+        .and_then(|(action, id)| Some((ReviewAction::parse(action)?, id.parse::<i64>().ok()?)))
+    else {
+        return Ok(());
+    };
+
+    let Some(guild_id) = component.guild_id else {
+        return Ok(());
+    };
+
+    let moderator_role = data.config.read().await.guild(guild_id).moderator_role;
+
+    if !is_moderator(component.member.as_ref(), moderator_role) {
+        return respond_ephemeral(
+            ctx,
+            component,
+            "You need the moderator role to review confessions.",
+        )
+        .await;
+    }
+
+    match action {
+        ReviewAction::Approve => approve(ctx, data, component, pending_id).await,
+        ReviewAction::Reject => reject(ctx, data, component, pending_id).await,
+        ReviewAction::Edit => edit(ctx, data, component, pending_id).await,
+    }
+}
+
+async fn respond_ephemeral(
+    ctx: &serenity::Context,
+    component: &ComponentInteraction,
+    content: &str,
+) -> Result<(), Error> {
+    component
+        .create_response(
+            ctx.http(),
+            CreateInteractionResponse::Message(
+                CreateInteractionResponseMessage::new()
+                    .allowed_mentions(no_mentions())
+                    .content(content)
+                    .ephemeral(true),
+            ),
+        )
+        .await?;
+    Ok(())
+}
+
+async fn approve(
+    ctx: &serenity::Context,
+    data: &Data,
+    component: &ComponentInteraction,
+    pending_id: i64,
+) -> Result<(), Error> {
+    let Some(pending) = data.storage.claim_pending(pending_id)? else {
+        return respond_ephemeral(ctx, component, "This confession has already been handled.")
+            .await;
+    };
+
+    // Creating the thread can take a moment, so acknowledge the button press first
+    component
+        .create_response(ctx.http(), CreateInteractionResponse::Acknowledge)
+        .await?;
+
+    match post_or_schedule(
+        pending.guild_id,
+        data,
+        ctx.http(),
+        &pending.author_hash,
+        pending.draft.clone(),
+        pending.created_at,
+    )
+    .await
+    {
+        Ok(posting) => {
+            data.storage.delete_pending(pending_id)?;
+
+            let status = match posting {
+                Posting::Published(published) => format!(
+                    "Approved by {} and posted as confession #{} in {}.",
+                    component.user.mention(),
+                    published.number,
+                    published
+                        .thread_id
+                        .unwrap_or(published.channel_id)
+                        .mention()
+                ),
+                Posting::Scheduled(post_at) => format!(
+                    "Approved by {} and queued to be posted <t:{}:R>.",
+                    component.user.mention(),
+                    post_at.timestamp()
+                ),
+            };
+            component
+                .edit_response(
+                    ctx.http(),
+                    EditInteractionResponse::new()
+                        .allowed_mentions(no_mentions())
+                        .embed(review_embed(
+                            pending_id,
+                            &pending.draft,
+                            Some((status, Color::DARK_GREEN)),
+                        ))
+                        .components(vec![]),
+                )
+                .await?;
+        }
+        Err(reply) => {
+            data.storage.release_pending(pending_id)?;
+
+            CreateInteractionResponseFollowup::new()
+                .allowed_mentions(no_mentions())
+                .content(reply)
+                .ephemeral(true)
+                .execute(ctx.http(), (None, &component.token))
+                .await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn reject(
+    ctx: &serenity::Context,
+    data: &Data,
+    component: &ComponentInteraction,
+    pending_id: i64,
+) -> Result<(), Error> {
+    let Some(pending) = data.storage.claim_pending(pending_id)? else {
+        return respond_ephemeral(ctx, component, "This confession has already been handled.")
+            .await;
+    };
+
+    data.storage.delete_pending(pending_id)?;
+
+    let audit_key = data.config.read().await.audit_key();
+    AuditEntry::confession(
+        pending.guild_id,
+        &pending.author_hash,
+        &pending.draft.content,
+        AuditOutcome::Rejected,
+    )
+    .log(audit_key.as_ref());
+
+    let status = format!("Rejected by {}.", component.user.mention());
+    component
+        .create_response(
+            ctx.http(),
+            CreateInteractionResponse::UpdateMessage(
+                CreateInteractionResponseMessage::new()
+                    .allowed_mentions(no_mentions())
+                    .embed(review_embed(
+                        pending_id,
+                        &pending.draft,
+                        Some((status, Color::RED)),
+                    ))
+                    .components(vec![]),
+            ),
+        )
+        .await?;
+
+    // Let the submitter know. Interaction tokens expire after 15 minutes, so this is best effort.
+    if let Err(e) = CreateInteractionResponseFollowup::new()
+        .allowed_mentions(no_mentions())
+        .content("Your anonymous confession was not approved by the moderators.")
+        .ephemeral(true)
+        .execute(ctx.http(), (None, &pending.interaction_token))
+        .await
+    {
+        log::info!(
+            "Could not notify the submitter of rejected confession {}: {:?}",
+            pending_id,
+            e
+        );
+    }
+
+    Ok(())
+}
+
+async fn edit(
+    ctx: &serenity::Context,
+    data: &Data,
+    component: &ComponentInteraction,
+    pending_id: i64,
+) -> Result<(), Error> {
+    let Some(pending) = data.storage.get_pending(pending_id)? else {
+        return respond_ephemeral(ctx, component, "This confession has already been handled.")
+            .await;
+    };
+
+    // Fields the guild's form doesn't show keep their submitted value
+    let form = data.config.read().await.guild(pending.guild_id).form;
+    let custom_id = component.id.to_string();
+    component
+        .create_response(
+            ctx.http(),
+            ConfessionModal::create(&form, Some(&pending.draft), custom_id.clone()),
+        )
+        .await?;
+
+    let response = serenity::collector::ModalInteractionCollector::new(&ctx.shard)
+        .filter(move |modal_interaction| modal_interaction.data.custom_id == custom_id)
+        .timeout(Duration::from_secs(3600))
+        .await;
+
+    let Some(modal_interaction) = response else {
+        return Ok(());
+    };
+
+    let Ok(modal) = ConfessionModal::parse(modal_interaction.data.clone()) else {
+        return Ok(());
+    };
+
+    let draft = modal.into_draft(pending.draft);
+
+    let response = if data.storage.update_pending_content(pending_id, &draft)? {
+        let status = format!("Awaiting review (edited by {})", component.user.mention());
+        CreateInteractionResponse::UpdateMessage(
+            CreateInteractionResponseMessage::new()
+                .allowed_mentions(no_mentions())
+                .embed(review_embed(
+                    pending_id,
+                    &draft,
+                    Some((status, Color::from_rgb(255, 165, 0))),
+                )),
+        )
+    } else {
+        CreateInteractionResponse::Message(
+            CreateInteractionResponseMessage::new()
+                .allowed_mentions(no_mentions())
+                .content("This confession has already been handled.")
+                .ephemeral(true),
+        )
+    };
+
+    modal_interaction
+        .create_response(ctx.http(), response)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use crate::{Context, Error};
+
+/// Rotate the secret used to derive anonymous author hashes.
+#[poise::command(
+    slash_command,
+    owners_only,
+    default_member_permissions = "ADMINISTRATOR",
+    description_localized(
+        "en-US",
+        "Rotate the secret used to derive anonymous author hashes (bot owners only)."
+    )
+)]
+pub async fn rotate_author_secret(ctx: Context<'_>) -> Result<(), Error> {
+    let data = ctx.data();
+    let mut config = data.config.write().await;
+    config.rotate_author_hash_secret();
+
+    // Save the updated configuration
+    if let Err(e) = config.save().await {
+        log::error!("Failed to save configuration: {:?}", e);
+        ctx.say(format!(
+            "Rotated the author hash secret but failed to save configuration: {:?}",
+            e
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    log::warn!("Author hash secret rotated by {}", ctx.author().id);
+
+    ctx.say(format!(
+        "Rotated the author hash secret. New confessions will get new author hashes; the previous secret is kept for {} days so recent abuse can still be traced.",
+        config.author_hash_lookup_days
+    ))
+    .await?;
+
+    Ok(())
+}
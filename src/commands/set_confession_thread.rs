@@ -1,6 +1,6 @@
 use poise::serenity_prelude::{self as serenity, ChannelId, Mentionable};
 
-use crate::{Context, Error};
+use crate::{Context, Error, commands::category::reconcile_forum_tags};
 
 /// Choose the guild channel (Text or Forum) where all confession threads/posts will be created.
 #[poise::command(
@@ -24,17 +24,20 @@ pub async fn set_confession_thread(
     // Check if the provided channel is a thread (or a channel that supports threads)
     let channel = thread_channel.to_channel(ctx.http()).await?;
 
-    if let serenity::Channel::Guild(guild_channel) = channel {
-        if !matches!(
-            guild_channel.kind,
-            serenity::ChannelType::Text | serenity::ChannelType::Forum
-        ) {
-            ctx.say("Error: The provided channel must be a Text channel or a Forum channel.")
+    let guild_channel = match channel {
+        serenity::Channel::Guild(guild_channel) => guild_channel,
+        _ => {
+            ctx.say("Error: The provided channel must be a guild channel.")
                 .await?;
             return Ok(());
         }
-    } else {
-        ctx.say("Error: The provided channel must be a guild channel.")
+    };
+
+    if !matches!(
+        guild_channel.kind,
+        serenity::ChannelType::Text | serenity::ChannelType::Forum
+    ) {
+        ctx.say("Error: The provided channel must be a Text channel or a Forum channel.")
             .await?;
         return Ok(());
     }
@@ -45,6 +48,12 @@ pub async fn set_confession_thread(
     let mut config = config_lock.write().await;
     config.confession_threads.insert(guild_id, thread_channel);
 
+    // Forget the configured forum tags the new channel doesn't have
+    let tag_notes = reconcile_forum_tags(
+        &guild_channel,
+        &mut config.guild_mut(guild_id).settings.forum_tags,
+    );
+
     // Save the updated configuration
     if let Err(e) = config.save().await {
         log::error!("Failed to save configuration: {:?}", e);
@@ -53,8 +62,9 @@ pub async fn set_confession_thread(
     }
 
     ctx.say(format!(
-        "Successfully set the confession channel for this guild to {}. New confessions will be created as threads/posts in this channel.",
-        thread_channel.mention()
+        "Successfully set the confession channel for this guild to {}. New confessions will be created as threads/posts in this channel.{}",
+        thread_channel.mention(),
+        tag_notes
     )).await?;
 
     Ok(())
@@ -1,20 +1,412 @@
-use std::path::Path;
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use log::LevelFilter;
+use poise::serenity_prelude::{
+    AutoArchiveDuration, ChannelId, ForumTagId, GuildId, RoleId, UserId,
+};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use poise::serenity_prelude::{ChannelId, GuildId};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
 use tokio::fs;
 
+use crate::{
+    anonymity::{author_hash, generate_secret},
+    appearance::Appearance,
+    audit::AuditKey,
+    filter::FilterConfig,
+};
+
 const CONFIG_FILE: &str = "config.json";
 
+fn default_author_hash_lookup_days() -> u32 {
+    30
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Config {
     pub discord_token: String,
     // Map of GuildId -> ChannelId (the thread where new confession threads are created)
     pub confession_threads: HashMap<GuildId, ChannelId>,
+    // Map of GuildId -> per-guild settings managed through slash commands
+    #[serde(default)]
+    pub guilds: HashMap<GuildId, GuildConfig>,
+    // Secret key for the HMAC deriving anonymous author hashes (generated on first start)
+    #[serde(default)]
+    pub author_hash_secret: String,
+    // Secrets replaced by a rotation, kept for `author_hash_lookup_days` so recent abuse can still be traced
+    #[serde(default)]
+    pub retired_author_hash_secrets: Vec<RetiredSecret>,
+    #[serde(default = "default_author_hash_lookup_days")]
+    pub author_hash_lookup_days: u32,
+    // Secret key for the HMAC chaining the moderation audit trail (generated on first start)
+    #[serde(default)]
+    pub moderation_audit_secret: String,
+    // Hex-encoded public key audit records are encrypted with (see `generate-audit-key`); plain text when unset
+    #[serde(default)]
+    pub audit_public_key: Option<String>,
+    // Audit entries older than this many days are purged every hour (0 keeps them forever)
+    #[serde(default)]
+    pub audit_retention_days: u32,
+    #[serde(default)]
+    pub audit_retention_action: RetentionAction,
+    #[serde(default)]
+    pub logging: LoggingSettings,
+}
+
+/// Where and how much the bot logs. Read once at startup; a `log4rs.yaml` file next to
+/// `config.json` replaces everything but `directory`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct LoggingSettings {
+    // Holds the audit log, the operational log and their rotated files
+    pub directory: PathBuf,
+    // Level of the operational log, and overrides for single modules such as `serenity`
+    pub level: LevelFilter,
+    pub modules: BTreeMap<String, LevelFilter>,
+    // Log files are rotated once they reach this size, keeping this many old files
+    pub rotation_size_mb: u64,
+    pub rotation_count: u32,
+    // Turn off to only log to files, e.g. when a service manager already keeps stdout
+    pub stdout: bool,
+}
+
+impl Default for LoggingSettings {
+    fn default() -> Self {
+        Self {
+            directory: PathBuf::from("logs"),
+            level: LevelFilter::Warn,
+            modules: BTreeMap::new(),
+            rotation_size_mb: 10,
+            rotation_count: 10,
+            stdout: true,
+        }
+    }
+}
+
+impl LoggingSettings {
+    /// Reads the `logging` section of `config.json` before logging is set up, falling back to
+    /// the defaults when the file is missing. Other problems with the file are reported once
+    /// the configuration is loaded.
+    pub fn read() -> Self {
+        std::fs::read_to_string(CONFIG_FILE)
+            .ok()
+            .and_then(|content| serde_json::from_str::<serde_json::Value>(&content).ok())
+            .and_then(|mut config| serde_json::from_value(config.get_mut("logging")?.take()).ok())
+            .unwrap_or_default()
+    }
+}
+
+/// An author hash secret that has been rotated out.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RetiredSecret {
+    pub secret: String,
+    pub retired_at: DateTime<Utc>,
+}
+
+/// Per-guild settings. Every field has a default so older configuration files keep loading.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct GuildConfig {
+    // Private channel where submissions wait for moderator approval (review mode is off when unset)
+    pub review_channel: Option<ChannelId>,
+    // Role allowed to use the moderation commands (e.g. revealing authors)
+    pub moderator_role: Option<RoleId>,
+    // How long a reveal request waits for a second moderator's approval
+    pub reveal_window_minutes: u32,
+    pub rate_limits: RateLimits,
+    pub attachments: AttachmentSettings,
+    pub form: FormSettings,
+    pub schedule: ScheduleSettings,
+    // Settings of the default board, whose channel is stored in `Config::confession_threads`
+    #[serde(flatten)]
+    pub settings: BoardSettings,
+    // Additional named boards, keyed by their lowercase name
+    pub boards: BTreeMap<String, Board>,
+}
+
+/// Whether images can be attached to confessions submitted with `/confess`.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct AttachmentSettings {
+    pub enabled: bool,
+    pub max_size_mb: u32,
+    // Hold confessions with an image for moderator approval, even if review mode is off
+    pub review: bool,
+}
+
+impl Default for AttachmentSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_size_mb: 8,
+            review: true,
+        }
+    }
+}
+
+/// Whether an optional field of the confession modal is shown, and whether it must be filled in.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter,
+)]
+pub enum FieldVisibility {
+    #[default]
+    Hidden,
+    Optional,
+    Required,
+}
+
+/// One of the optional fields of the confession modal.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+pub struct FormField {
+    pub visibility: FieldVisibility,
+    pub max_length: u16,
+}
+
+/// Which fields the confession modal asks for, besides the confession itself.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct FormSettings {
+    pub content_max_length: u16,
+    // Used as the name of the thread or forum post instead of the thread name template
+    pub title: FormField,
+    pub content_warning: FormField,
+    // Pseudonym the author signs the confession with
+    pub signature: FormField,
+    // Show authors how their confession will look before it is submitted
+    pub preview: bool,
+}
+
+impl Default for FormSettings {
+    fn default() -> Self {
+        Self {
+            content_max_length: 2000,
+            title: FormField {
+                visibility: FieldVisibility::Hidden,
+                max_length: 100,
+            },
+            content_warning: FormField {
+                visibility: FieldVisibility::Optional,
+                max_length: 100,
+            },
+            signature: FormField {
+                visibility: FieldVisibility::Hidden,
+                max_length: 32,
+            },
+            preview: true,
+        }
+    }
+}
+
+/// Everything that can differ between the confession boards of a guild.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct BoardSettings {
+    pub filter: FilterConfig,
+    pub appearance: Appearance,
+    pub threads: ThreadSettings,
+    pub forum_tags: ForumTags,
+}
+
+/// A named confession board (e.g. "vent") posting to its own channel.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Board {
+    pub channel: ChannelId,
+    #[serde(flatten)]
+    pub settings: BoardSettings,
+}
+
+/// Limits on how often confessions can be submitted. A value of 0 disables that limit.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct RateLimits {
+    // Minimum time between two confessions from the same user
+    pub user_cooldown_minutes: u32,
+    // Maximum confessions per user within any 24 hours
+    pub user_daily_limit: u32,
+    // Maximum confessions for the whole guild within `guild_burst_window_minutes`
+    pub guild_burst_limit: u32,
+    pub guild_burst_window_minutes: u32,
+}
+
+/// What happens to audit entries that are purged.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter,
+)]
+pub enum RetentionAction {
+    #[default]
+    #[name = "Delete the entries"]
+    Delete,
+    #[name = "Remove the author hash and content"]
+    Redact,
+}
+
+/// When confessions are posted once they are submitted (or approved, in review mode).
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter,
+)]
+pub enum ScheduleMode {
+    #[default]
+    #[name = "Immediately"]
+    Immediate,
+    #[name = "After a random delay"]
+    RandomDelay,
+    #[name = "At fixed batch times"]
+    Batches,
+}
+
+/// Delays confessions so that the time they appear doesn't point to whoever was just online.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(default)]
+pub struct ScheduleSettings {
+    pub mode: ScheduleMode,
+    // Bounds of the random delay. In batch mode, the least time before the batch a confession
+    // goes into, so that it doesn't appear right after being submitted.
+    pub min_delay_minutes: u32,
+    pub max_delay_minutes: u32,
+    // Times of day (UTC) at which batches are posted
+    pub batch_times: Vec<NaiveTime>,
+}
+
+impl Default for ScheduleSettings {
+    fn default() -> Self {
+        Self {
+            mode: ScheduleMode::Immediate,
+            min_delay_minutes: 5,
+            max_delay_minutes: 30,
+            batch_times: Vec::new(),
+        }
+    }
+}
+
+/// Whether confessions in a Text channel get their own thread. Forum channels always create a post.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter,
+)]
+pub enum PostMode {
+    #[default]
+    #[name = "Thread per confession"]
+    Thread,
+    #[name = "Embed only"]
+    EmbedOnly,
+}
+
+/// Inactivity after which confession threads are archived.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter,
+)]
+pub enum ArchiveAfter {
+    #[name = "1 hour"]
+    OneHour,
+    #[name = "1 day"]
+    OneDay,
+    #[default]
+    #[name = "3 days"]
+    ThreeDays,
+    #[name = "1 week"]
+    OneWeek,
+}
+
+impl From<ArchiveAfter> for AutoArchiveDuration {
+    fn from(archive_after: ArchiveAfter) -> Self {
+        match archive_after {
+            ArchiveAfter::OneHour => AutoArchiveDuration::OneHour,
+            ArchiveAfter::OneDay => AutoArchiveDuration::OneDay,
+            ArchiveAfter::ThreeDays => AutoArchiveDuration::ThreeDays,
+            ArchiveAfter::OneWeek => AutoArchiveDuration::OneWeek,
+        }
+    }
+}
+
+/// How the threads and forum posts of confessions are created.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ThreadSettings {
+    pub mode: PostMode,
+    pub archive_after: ArchiveAfter,
+    // Locked threads are read-only for everyone but moderators
+    pub locked: bool,
+    // Seconds members have to wait between two messages in the thread (0 turns slowmode off)
+    pub slowmode_seconds: u16,
+}
+
+/// Tags applied to confession posts when the confession channel is a Forum channel.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ForumTags {
+    // Added to every confession post
+    pub default_tag: Option<ForumTagId>,
+    // Tags authors can choose from before writing their confession
+    pub categories: Vec<ForumTagId>,
+    // Added to confession posts with a content warning
+    pub content_warning_tag: Option<ForumTagId>,
+}
+
+impl ForumTags {
+    /// Drops the tags the forum doesn't offer (anymore) and returns them.
+    pub fn retain_available(&mut self, available: &[ForumTagId]) -> Vec<ForumTagId> {
+        let mut removed = Vec::new();
+
+        if let Some(tag) = self.default_tag
+            && !available.contains(&tag)
+        {
+            removed.push(tag);
+            self.default_tag = None;
+        }
+
+        if let Some(tag) = self.content_warning_tag
+            && !available.contains(&tag)
+        {
+            removed.push(tag);
+            self.content_warning_tag = None;
+        }
+
+        self.categories.retain(|tag| {
+            let keep = available.contains(tag);
+            if !keep {
+                removed.push(*tag);
+            }
+            keep
+        });
+
+        removed
+    }
+}
+
+impl Default for GuildConfig {
+    fn default() -> Self {
+        Self {
+            review_channel: None,
+            moderator_role: None,
+            reveal_window_minutes: 60,
+            rate_limits: RateLimits::default(),
+            attachments: AttachmentSettings::default(),
+            form: FormSettings::default(),
+            schedule: ScheduleSettings::default(),
+            settings: BoardSettings::default(),
+            boards: BTreeMap::new(),
+        }
+    }
+}
+
+impl GuildConfig {
+    /// Returns the settings of the named board, or of the default board when `board` is `None`.
+    pub fn board_settings(&self, board: Option<&str>) -> Option<&BoardSettings> {
+        match board {
+            None => Some(&self.settings),
+            Some(name) => self.boards.get(name).map(|board| &board.settings),
+        }
+    }
+
+    /// Mutable version of [`GuildConfig::board_settings`].
+    pub fn board_settings_mut(&mut self, board: Option<&str>) -> Option<&mut BoardSettings> {
+        match board {
+            None => Some(&mut self.settings),
+            Some(name) => self.boards.get_mut(name).map(|board| &mut board.settings),
+        }
+    }
 }
 
 impl Config {
-    /// Loads the configuration from `config.json`. If the file doesn't exist, 
+    /// Loads the configuration from `config.json`. If the file doesn't exist,
     /// it creates a default one and prompts the user to fill it.
     pub async fn load() -> Result<Self, Box<dyn std::error::Error + Send + Sync>> {
         let path = Path::new(CONFIG_FILE);
@@ -23,27 +415,136 @@ impl Config {
             let default_config = Config {
                 discord_token: "YOUR_BOT_TOKEN_HERE".to_string(),
                 confession_threads: HashMap::new(),
+                guilds: HashMap::new(),
+                author_hash_secret: generate_secret(),
+                retired_author_hash_secrets: Vec::new(),
+                author_hash_lookup_days: default_author_hash_lookup_days(),
+                moderation_audit_secret: generate_secret(),
+                audit_public_key: None,
+                audit_retention_days: 0,
+                audit_retention_action: RetentionAction::default(),
+                logging: LoggingSettings::default(),
             };
             default_config.save().await?;
-            
-            eprintln!("Created default {}. Please fill in your bot token.", CONFIG_FILE);
+
+            eprintln!(
+                "Created default {}. Please fill in your bot token.",
+                CONFIG_FILE
+            );
             return Err("Configuration file created. Please update it and restart.".into());
         }
 
         let content = fs::read_to_string(path).await?;
-        let config: Config = serde_json::from_str(&content)?;
-        
+        let mut config: Config = serde_json::from_str(&content)?;
+
         if config.discord_token == "YOUR_BOT_TOKEN_HERE" {
-            return Err("Please replace YOUR_BOT_TOKEN_HERE in config.json with your actual bot token.".into());
+            return Err(
+                "Please replace YOUR_BOT_TOKEN_HERE in config.json with your actual bot token."
+                    .into(),
+            );
+        }
+
+        // Configurations from before author hashes were keyed don't have a secret yet
+        if config.author_hash_secret.is_empty() {
+            config.author_hash_secret = generate_secret();
+            config.save().await?;
+            log::info!("Generated a new author hash secret.");
+        }
+
+        // Configurations from before the moderation audit trail was keyed don't have one either
+        if config.moderation_audit_secret.is_empty() {
+            config.moderation_audit_secret = generate_secret();
+            config.save().await?;
+            log::info!("Generated a new moderation audit secret.");
+        }
+
+        if let Some(key) = &config.audit_public_key {
+            AuditKey::from_hex(key).map_err(|e| format!("Invalid audit_public_key: {}", e))?;
         }
 
         Ok(config)
     }
 
+    /// Returns the settings for the given guild, falling back to the defaults.
+    pub fn guild(&self, guild_id: GuildId) -> GuildConfig {
+        self.guilds.get(&guild_id).cloned().unwrap_or_default()
+    }
+
+    /// Returns the channel and settings of a board of the guild (the default board when `board`
+    /// is `None`). Returns `None` if there is no such board or the default board has no channel.
+    pub fn board(&self, guild_id: GuildId, board: Option<&str>) -> Option<Board> {
+        let guild_config = self.guilds.get(&guild_id);
+
+        match board {
+            None => Some(Board {
+                channel: *self.confession_threads.get(&guild_id)?,
+                settings: guild_config
+                    .map(|guild_config| guild_config.settings.clone())
+                    .unwrap_or_default(),
+            }),
+            Some(name) => guild_config?.boards.get(name).cloned(),
+        }
+    }
+
+    /// Returns a mutable reference to the settings for the given guild, creating them if needed.
+    pub fn guild_mut(&mut self, guild_id: GuildId) -> &mut GuildConfig {
+        self.guilds.entry(guild_id).or_default()
+    }
+
+    /// Returns the key audit records are encrypted with, if one is configured. The key is
+    /// validated when the configuration is loaded.
+    pub fn audit_key(&self) -> Option<AuditKey> {
+        AuditKey::from_hex(self.audit_public_key.as_deref()?).ok()
+    }
+
+    /// Returns the anonymous hash of a user within a guild, derived from the current secret.
+    pub fn author_hash(&self, guild_id: GuildId, user_id: UserId) -> String {
+        author_hash(&self.author_hash_secret, guild_id, user_id)
+    }
+
+    /// Returns every hash the user may have been recorded under: the one from the current secret
+    /// and those from retired secrets still inside the lookup window.
+    pub fn author_hashes(&self, guild_id: GuildId, user_id: UserId) -> Vec<String> {
+        let cutoff = Utc::now() - Duration::days(self.author_hash_lookup_days.into());
+
+        std::iter::once(self.author_hash_secret.as_str())
+            .chain(
+                self.retired_author_hash_secrets
+                    .iter()
+                    .filter(|retired| retired.retired_at >= cutoff)
+                    .map(|retired| retired.secret.as_str()),
+            )
+            .map(|secret| author_hash(secret, guild_id, user_id))
+            .collect()
+    }
+
+    /// Returns whether the hash belongs to the user within the lookup window.
+    pub fn is_author_hash_of(&self, hash: &str, guild_id: GuildId, user_id: UserId) -> bool {
+        self.author_hashes(guild_id, user_id)
+            .iter()
+            .any(|candidate| candidate == hash)
+    }
+
+    /// Replaces the author hash secret with a freshly generated one.
+    /// The old secret is kept for the lookup window and secrets past it are dropped.
+    pub fn rotate_author_hash_secret(&mut self) {
+        let now = Utc::now();
+        let retired = std::mem::replace(&mut self.author_hash_secret, generate_secret());
+
+        self.retired_author_hash_secrets.push(RetiredSecret {
+            secret: retired,
+            retired_at: now,
+        });
+
+        let cutoff = now - Duration::days(self.author_hash_lookup_days.into());
+        self.retired_author_hash_secrets
+            .retain(|retired| retired.retired_at >= cutoff);
+    }
+
     /// Saves the current configuration state to `config.json`.
     pub async fn save(&self) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         let content = serde_json::to_string_pretty(self)?;
         fs::write(CONFIG_FILE, content).await?;
         Ok(())
     }
-}
\ No newline at end of file
+}
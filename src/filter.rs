@@ -0,0 +1,633 @@
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::sanitize::MentionMode;
+
+// Replacement for redacted words, links and mentions
+const REDACTED: &str = "[redacted]";
+
+/// What happens to a confession when a rule matches.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, poise::ChoiceParameter)]
+pub enum FilterAction {
+    /// Refuse the confession.
+    Reject,
+    /// Send the confession to the review queue, even if review mode is off.
+    Hold,
+    /// Replace the matching text and post the rest.
+    Redact,
+    /// Only accept the confession if it has a content warning.
+    #[name = "Require content warning"]
+    RequireWarning,
+}
+
+/// How links (or invites) in confessions are treated.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter,
+)]
+pub enum LinkPolicy {
+    #[default]
+    Allow,
+    Strip,
+    Block,
+}
+
+/// A word or phrase that may not appear in confessions (matched case-insensitively).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct WordRule {
+    pub word: String,
+    pub action: FilterAction,
+}
+
+/// A regular expression that may not match confessions.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct RegexRule {
+    pub pattern: String,
+    pub action: FilterAction,
+}
+
+/// Per-guild content filter rules.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(default)]
+pub struct FilterConfig {
+    pub blocked_words: Vec<WordRule>,
+    pub regex_rules: Vec<RegexRule>,
+    pub link_policy: LinkPolicy,
+    // Applied to Discord invites instead of `link_policy`
+    pub invite_policy: LinkPolicy,
+    // Maximum user mentions per confession (0 means unlimited)
+    pub max_mentions: u32,
+    pub allow_mass_mentions: bool,
+    pub allow_role_mentions: bool,
+    pub mention_action: FilterAction,
+    // Applied to the mentions left in a confession when it is posted
+    pub mention_mode: MentionMode,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self {
+            blocked_words: Vec::new(),
+            regex_rules: Vec::new(),
+            link_policy: LinkPolicy::Allow,
+            invite_policy: LinkPolicy::Allow,
+            max_mentions: 0,
+            allow_mass_mentions: true,
+            allow_role_mentions: true,
+            mention_action: FilterAction::Reject,
+            mention_mode: MentionMode::Escape,
+        }
+    }
+}
+
+/// The strongest decision reached by the filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Verdict {
+    Allow,
+    Hold(String),
+    Reject(String),
+}
+
+impl Verdict {
+    /// Returns the stronger of two verdicts, preferring `self` when they are equally strong.
+    pub fn strongest(self, other: Verdict) -> Verdict {
+        match (&self, &other) {
+            (Verdict::Reject(_), _) => self,
+            (_, Verdict::Reject(_)) => other,
+            (Verdict::Hold(_), _) => self,
+            (_, Verdict::Hold(_)) => other,
+            _ => self,
+        }
+    }
+}
+
+/// The result of running a confession through the filter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilterOutcome {
+    // The confession with redactions and stripped links applied
+    pub content: String,
+    pub verdict: Verdict,
+    // Why the confession needs a content warning, if a matching rule requires one
+    pub needs_warning: Option<String>,
+}
+
+/// Compiles a user-supplied rule pattern (case-insensitive).
+pub fn compile_pattern(pattern: &str) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .size_limit(1 << 20)
+        .build()
+}
+
+/// Builds a pattern matching the word on its own, not as part of a longer word.
+fn word_pattern(word: &str) -> String {
+    let word = word.trim();
+    let is_word_char = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '_');
+
+    format!(
+        "{}{}{}",
+        if is_word_char(word.chars().next()) {
+            r"\b"
+        } else {
+            ""
+        },
+        regex::escape(word),
+        if is_word_char(word.chars().last()) {
+            r"\b"
+        } else {
+            ""
+        },
+    )
+}
+
+// Characters a link may be followed by at the end of a sentence
+const TRAILING_PUNCTUATION: &str = ".,!?;:)'\"";
+
+fn invite_regex() -> Regex {
+    Regex::new(r"(?i)(?:https?://)?(?:www\.)?(?:discord(?:app)?\.com/invite|discord\.gg)/[\w-]+")
+        .expect("Invalid invite regex")
+}
+
+fn link_regex() -> Regex {
+    Regex::new(r"(?i)\bhttps?://[^\s<>]+|\bwww\.[^\s<>]+").expect("Invalid link regex")
+}
+
+fn user_mention_regex() -> Regex {
+    Regex::new(r"<@!?\d+>").expect("Invalid user mention regex")
+}
+
+fn role_mention_regex() -> Regex {
+    Regex::new(r"<@&\d+>").expect("Invalid role mention regex")
+}
+
+fn mass_mention_regex() -> Regex {
+    Regex::new(r"@(?:everyone|here)\b").expect("Invalid mass mention regex")
+}
+
+/// Runs a confession through the filter rules, in order: blocked words, regex rules,
+/// invites, links and mentions.
+pub fn apply(config: &FilterConfig, content: &str) -> FilterOutcome {
+    let mut filter = Filter {
+        content: content.to_string(),
+        verdict: Verdict::Allow,
+        needs_warning: None,
+    };
+
+    for rule in &config.blocked_words {
+        if let Ok(regex) = compile_pattern(&word_pattern(&rule.word)) {
+            filter.rule(&regex, rule.action, "a blocked word");
+        }
+    }
+
+    for rule in &config.regex_rules {
+        match compile_pattern(&rule.pattern) {
+            Ok(regex) => filter.rule(&regex, rule.action, "a filtered phrase"),
+            Err(e) => log::warn!("Skipping invalid filter rule {:?}: {}", rule.pattern, e),
+        }
+    }
+
+    // Invites left in by their own policy are not links as far as `link_policy` is concerned
+    let invites = invite_regex();
+    filter.links(&invites, config.invite_policy, "Discord invites", None);
+    filter.links(&link_regex(), config.link_policy, "links", Some(&invites));
+
+    if !config.allow_mass_mentions {
+        filter.rule(
+            &mass_mention_regex(),
+            config.mention_action,
+            "@everyone/@here mentions",
+        );
+    }
+
+    if !config.allow_role_mentions {
+        filter.rule(
+            &role_mention_regex(),
+            config.mention_action,
+            "role mentions",
+        );
+    }
+
+    if config.max_mentions > 0 {
+        let user_mentions = user_mention_regex();
+
+        if user_mentions.find_iter(&filter.content).count() > config.max_mentions as usize {
+            let reason = format!("more than {} mentions", config.max_mentions);
+
+            match config.mention_action {
+                // Keep the first mentions that fit within the limit
+                FilterAction::Redact => {
+                    let mut seen = 0;
+                    filter.content = user_mentions
+                        .replace_all(&filter.content, |captures: &regex::Captures| {
+                            seen += 1;
+                            if seen > config.max_mentions {
+                                REDACTED.to_string()
+                            } else {
+                                captures[0].to_string()
+                            }
+                        })
+                        .into_owned();
+                }
+                action => filter.escalate(action, &reason),
+            }
+        }
+    }
+
+    FilterOutcome {
+        content: filter.content.trim().to_string(),
+        verdict: filter.verdict,
+        needs_warning: filter.needs_warning,
+    }
+}
+
+struct Filter {
+    content: String,
+    verdict: Verdict,
+    needs_warning: Option<String>,
+}
+
+impl Filter {
+    /// Applies the action of a rule if its regex matches the current content.
+    fn rule(&mut self, regex: &Regex, action: FilterAction, reason: &str) {
+        if !regex.is_match(&self.content) {
+            return;
+        }
+
+        match action {
+            FilterAction::Redact => {
+                self.content = regex.replace_all(&self.content, REDACTED).into_owned();
+            }
+            action => self.escalate(action, reason),
+        }
+    }
+
+    /// Applies a link policy to every match of the regex, except those that `except` matches
+    /// as a whole (apart from punctuation ending the sentence).
+    fn links(&mut self, regex: &Regex, policy: LinkPolicy, reason: &str, except: Option<&Regex>) {
+        let excepted = |link: &str| {
+            except
+                .and_then(|except| except.find(link))
+                .is_some_and(|found| {
+                    found.start() == 0
+                        && link[found.end()..]
+                            .chars()
+                            .all(|c| TRAILING_PUNCTUATION.contains(c))
+                })
+        };
+
+        if !regex
+            .find_iter(&self.content)
+            .any(|link| !excepted(link.as_str()))
+        {
+            return;
+        }
+
+        match policy {
+            LinkPolicy::Allow => {}
+            LinkPolicy::Strip => {
+                self.content = regex
+                    .replace_all(&self.content, |captures: &regex::Captures| {
+                        if excepted(&captures[0]) {
+                            captures[0].to_string()
+                        } else {
+                            String::new()
+                        }
+                    })
+                    .into_owned();
+            }
+            LinkPolicy::Block => self.escalate(FilterAction::Reject, reason),
+        }
+    }
+
+    /// Raises the verdict to the given action, keeping the first reason of the strongest one.
+    fn escalate(&mut self, action: FilterAction, reason: &str) {
+        if action == FilterAction::RequireWarning {
+            self.needs_warning.get_or_insert_with(|| reason.to_string());
+            return;
+        }
+
+        self.verdict = match (&self.verdict, action) {
+            (Verdict::Reject(_), _) => return,
+            (_, FilterAction::Reject) => Verdict::Reject(reason.to_string()),
+            (Verdict::Hold(_), _) => return,
+            (_, FilterAction::Hold) => Verdict::Hold(reason.to_string()),
+            (_, FilterAction::Redact | FilterAction::RequireWarning) => return,
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(rules: &[(&str, FilterAction)]) -> FilterConfig {
+        FilterConfig {
+            blocked_words: rules
+                .iter()
+                .map(|(word, action)| WordRule {
+                    word: word.to_string(),
+                    action: *action,
+                })
+                .collect(),
+            ..FilterConfig::default()
+        }
+    }
+
+    fn regexes(rules: &[(&str, FilterAction)]) -> FilterConfig {
+        FilterConfig {
+            regex_rules: rules
+                .iter()
+                .map(|(pattern, action)| RegexRule {
+                    pattern: pattern.to_string(),
+                    action: *action,
+                })
+                .collect(),
+            ..FilterConfig::default()
+        }
+    }
+
+    fn links(link_policy: LinkPolicy, invite_policy: LinkPolicy) -> FilterConfig {
+        FilterConfig {
+            link_policy,
+            invite_policy,
+            ..FilterConfig::default()
+        }
+    }
+
+    #[test]
+    fn allows_clean_content() {
+        let outcome = apply(&FilterConfig::default(), "  nothing to see here  ");
+        assert_eq!(outcome.content, "nothing to see here");
+        assert_eq!(outcome.verdict, Verdict::Allow);
+        assert_eq!(outcome.needs_warning, None);
+    }
+
+    #[test]
+    fn word_rule_rejects() {
+        let outcome = apply(
+            &words(&[("secret", FilterAction::Reject)]),
+            "My SECRET is out",
+        );
+        assert_eq!(
+            outcome.verdict,
+            Verdict::Reject("a blocked word".to_string())
+        );
+    }
+
+    #[test]
+    fn word_rule_holds() {
+        let outcome = apply(&words(&[("secret", FilterAction::Hold)]), "my secret");
+        assert_eq!(outcome.verdict, Verdict::Hold("a blocked word".to_string()));
+    }
+
+    #[test]
+    fn word_rule_redacts() {
+        let outcome = apply(
+            &words(&[("secret", FilterAction::Redact)]),
+            "a secret, a Secret",
+        );
+        assert_eq!(outcome.content, "a [redacted], a [redacted]");
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn word_rule_requires_warning() {
+        let outcome = apply(
+            &words(&[("secret", FilterAction::RequireWarning)]),
+            "my secret",
+        );
+        assert_eq!(outcome.verdict, Verdict::Allow);
+        assert_eq!(outcome.needs_warning, Some("a blocked word".to_string()));
+    }
+
+    #[test]
+    fn word_rule_matches_whole_words_only() {
+        let outcome = apply(
+            &words(&[("ass", FilterAction::Reject)]),
+            "a classic passage",
+        );
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn reject_wins_over_hold() {
+        let config = words(&[
+            ("maybe", FilterAction::Hold),
+            ("never", FilterAction::Reject),
+        ]);
+        let outcome = apply(&config, "maybe never");
+        assert_eq!(
+            outcome.verdict,
+            Verdict::Reject("a blocked word".to_string())
+        );
+    }
+
+    #[test]
+    fn regex_rule_rejects() {
+        let outcome = apply(
+            &regexes(&[(r"\d{3}-\d{4}", FilterAction::Reject)]),
+            "call 555-1234",
+        );
+        assert_eq!(
+            outcome.verdict,
+            Verdict::Reject("a filtered phrase".to_string())
+        );
+    }
+
+    #[test]
+    fn regex_rule_holds() {
+        let outcome = apply(
+            &regexes(&[(r"\d{3}-\d{4}", FilterAction::Hold)]),
+            "call 555-1234",
+        );
+        assert_eq!(
+            outcome.verdict,
+            Verdict::Hold("a filtered phrase".to_string())
+        );
+    }
+
+    #[test]
+    fn regex_rule_redacts() {
+        let outcome = apply(
+            &regexes(&[(r"\d{3}-\d{4}", FilterAction::Redact)]),
+            "call 555-1234",
+        );
+        assert_eq!(outcome.content, "call [redacted]");
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn invalid_regex_rule_is_skipped() {
+        let outcome = apply(
+            &regexes(&[("(unclosed", FilterAction::Reject)]),
+            "(unclosed",
+        );
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn links_allowed() {
+        let outcome = apply(
+            &links(LinkPolicy::Allow, LinkPolicy::Allow),
+            "see https://example.com",
+        );
+        assert_eq!(outcome.content, "see https://example.com");
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn links_stripped() {
+        let outcome = apply(
+            &links(LinkPolicy::Strip, LinkPolicy::Allow),
+            "see https://example.com and www.example.org now",
+        );
+        assert_eq!(outcome.content, "see  and  now");
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn links_blocked() {
+        let outcome = apply(
+            &links(LinkPolicy::Block, LinkPolicy::Allow),
+            "see https://example.com",
+        );
+        assert_eq!(outcome.verdict, Verdict::Reject("links".to_string()));
+    }
+
+    #[test]
+    fn invites_allowed() {
+        let outcome = apply(
+            &links(LinkPolicy::Allow, LinkPolicy::Allow),
+            "join discord.gg/abc",
+        );
+        assert_eq!(outcome.content, "join discord.gg/abc");
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn invites_stripped() {
+        let outcome = apply(
+            &links(LinkPolicy::Allow, LinkPolicy::Strip),
+            "join https://discord.gg/abc now",
+        );
+        assert_eq!(outcome.content, "join  now");
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn invites_blocked() {
+        let outcome = apply(
+            &links(LinkPolicy::Allow, LinkPolicy::Block),
+            "join discord.com/invite/abc",
+        );
+        assert_eq!(
+            outcome.verdict,
+            Verdict::Reject("Discord invites".to_string())
+        );
+    }
+
+    #[test]
+    fn allowed_invites_are_not_treated_as_links() {
+        let content = "join https://discord.gg/abc";
+        let outcome = apply(&links(LinkPolicy::Block, LinkPolicy::Allow), content);
+        assert_eq!(outcome.verdict, Verdict::Allow);
+
+        let outcome = apply(
+            &links(LinkPolicy::Strip, LinkPolicy::Allow),
+            "join https://discord.gg/abc or https://example.com",
+        );
+        assert_eq!(outcome.content, "join https://discord.gg/abc or");
+
+        let outcome = apply(
+            &links(LinkPolicy::Block, LinkPolicy::Allow),
+            "join https://discord.gg/abc or https://example.com",
+        );
+        assert_eq!(outcome.verdict, Verdict::Reject("links".to_string()));
+
+        let outcome = apply(
+            &links(LinkPolicy::Block, LinkPolicy::Allow),
+            "join https://discord.gg/abc.",
+        );
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn links_merely_containing_an_invite_are_still_links() {
+        for content in [
+            "see https://evil.example/discord.gg/x",
+            "see https://discord.gg/abc/../../evil",
+            "see www.evil.example/discord.com/invite/x",
+        ] {
+            let outcome = apply(&links(LinkPolicy::Block, LinkPolicy::Allow), content);
+            assert_eq!(
+                outcome.verdict,
+                Verdict::Reject("links".to_string()),
+                "{:?}",
+                content
+            );
+
+            let outcome = apply(&links(LinkPolicy::Strip, LinkPolicy::Allow), content);
+            assert_eq!(outcome.content, "see", "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn mention_limit_rejects() {
+        let config = FilterConfig {
+            max_mentions: 2,
+            ..FilterConfig::default()
+        };
+        let outcome = apply(&config, "<@1> <@!2> <@3>");
+        assert_eq!(
+            outcome.verdict,
+            Verdict::Reject("more than 2 mentions".to_string())
+        );
+    }
+
+    #[test]
+    fn mention_limit_allows_up_to_the_limit() {
+        let config = FilterConfig {
+            max_mentions: 2,
+            ..FilterConfig::default()
+        };
+        let outcome = apply(&config, "<@1> <@2>");
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn mention_limit_holds() {
+        let config = FilterConfig {
+            max_mentions: 1,
+            mention_action: FilterAction::Hold,
+            ..FilterConfig::default()
+        };
+        let outcome = apply(&config, "<@1> <@2>");
+        assert_eq!(
+            outcome.verdict,
+            Verdict::Hold("more than 1 mentions".to_string())
+        );
+    }
+
+    #[test]
+    fn mention_limit_redacts_mentions_past_the_limit() {
+        let config = FilterConfig {
+            max_mentions: 1,
+            mention_action: FilterAction::Redact,
+            ..FilterConfig::default()
+        };
+        let outcome = apply(&config, "<@1> <@2> <@3>");
+        assert_eq!(outcome.content, "<@1> [redacted] [redacted]");
+        assert_eq!(outcome.verdict, Verdict::Allow);
+    }
+
+    #[test]
+    fn mass_and_role_mentions() {
+        let config = FilterConfig {
+            allow_mass_mentions: false,
+            allow_role_mentions: false,
+            mention_action: FilterAction::Redact,
+            ..FilterConfig::default()
+        };
+        let outcome = apply(&config, "@everyone and <@&5>");
+        assert_eq!(outcome.content, "[redacted] and [redacted]");
+    }
+}
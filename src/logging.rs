@@ -1,9 +1,304 @@
-/// Logs a confession event for auditing purposes.
-/// The log includes the hash and the content.
-pub fn log_confession(hash: &str, content: &str) {
-    log::warn!(
-        "Confession received: {} | {}",
-        hash,
-        content.replace('\n', " \\n ")
-    );
+use chrono::{DateTime, Utc};
+use log::LevelFilter;
+use log4rs::{
+    append::{
+        console::ConsoleAppender,
+        rolling_file::{
+            RollingFileAppender,
+            policy::compound::{
+                CompoundPolicy, roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger,
+            },
+        },
+    },
+    config::{Appender, Config as Log4rsConfig, Logger, Root},
+    encode::pattern::PatternEncoder,
+};
+use poise::serenity_prelude::{ChannelId, GuildId};
+use serde::Serialize;
+use std::{
+    path::Path,
+    sync::{Mutex, MutexGuard, OnceLock, PoisonError},
+};
+
+use crate::{Error, audit::AuditKey, config::LoggingSettings};
+
+// External log4rs configuration used instead of the `logging` section of `config.json`
+const LOG4RS_CONFIG_FILE: &str = "log4rs.yaml";
+
+// File names of the audit log and the operational log, without the `.log` extension
+pub const AUDIT_LOG_NAME: &str = "confessions_audit";
+const BOT_LOG_NAME: &str = "confessions_bot";
+
+// Line format of the operational logs
+const LOG_PATTERN: &str = "[{d(%Y-%m-%dT%H:%M:%S%z)}] | {l} | {M} | {m}\n";
+
+/// Log target of the audit trail. log4rs writes it to its own file, one JSON object per line,
+/// apart from the operational logs.
+pub const AUDIT_TARGET: &str = "audit";
+
+// Held while an entry is written and while old entries are purged, so that a purge rewriting
+// the audit log doesn't drop entries written in the meantime
+static AUDIT_LOG_LOCK: Mutex<()> = Mutex::new(());
+
+// Handle to reconfigure log4rs with, and the settings it was set up from
+static LOGGER: OnceLock<(log4rs::Handle, LoggingSettings)> = OnceLock::new();
+
+/// Locks the audit log against new entries.
+pub fn lock_audit_log() -> MutexGuard<'static, ()> {
+    AUDIT_LOG_LOCK
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Builds a file appender writing to `<directory>/<name>.log`, rolled over into
+/// `<directory>/<name>.<n>.log` once it reaches the configured size.
+fn rolling_file_appender(
+    settings: &LoggingSettings,
+    name: &str,
+    pattern: &str,
+) -> RollingFileAppender {
+    let directory = &settings.directory;
+
+    // The roller needs at least one old file to roll into
+    let window_roller = FixedWindowRoller::builder()
+        .base(1)
+        .build(
+            &directory
+                .join(format!("{}.{{}}.log", name))
+                .to_string_lossy(),
+            settings.rotation_count.max(1),
+        )
+        .expect("Failed to build window roller");
+
+    let size_trigger = SizeTrigger::new(settings.rotation_size_mb.max(1) * 1024 * 1024);
+
+    let compound_policy = CompoundPolicy::new(Box::new(size_trigger), Box::new(window_roller));
+
+    RollingFileAppender::builder()
+        .encoder(Box::new(PatternEncoder::new(pattern)))
+        .build(
+            directory.join(format!("{}.log", name)),
+            Box::new(compound_policy),
+        )
+        .expect("Failed to build rolling file appender")
+}
+
+/// Builds the log4rs configuration: from `log4rs.yaml` if present, otherwise the audit trail
+/// goes to its own file as JSON lines, and operational logs to another file and stdout.
+fn build_config(settings: &LoggingSettings) -> Result<Log4rsConfig, Error> {
+    if Path::new(LOG4RS_CONFIG_FILE).exists() {
+        return Ok(log4rs::config::load_config_file(
+            LOG4RS_CONFIG_FILE,
+            Default::default(),
+        )?);
+    }
+
+    let mut builder = Log4rsConfig::builder()
+        .appender(Appender::builder().build(
+            "audit",
+            Box::new(rolling_file_appender(settings, AUDIT_LOG_NAME, "{m}{n}")),
+        ))
+        .appender(Appender::builder().build(
+            "file",
+            Box::new(rolling_file_appender(settings, BOT_LOG_NAME, LOG_PATTERN)),
+        ))
+        .logger(
+            Logger::builder()
+                .appender("audit")
+                .additive(false)
+                .build(AUDIT_TARGET, LevelFilter::Info),
+        );
+
+    let mut root = Root::builder().appender("file");
+
+    if settings.stdout {
+        let stdout_appender = ConsoleAppender::builder()
+            .encoder(Box::new(PatternEncoder::new(LOG_PATTERN)))
+            .build();
+        builder = builder.appender(Appender::builder().build("stdout", Box::new(stdout_appender)));
+        root = root.appender("stdout");
+    }
+
+    // Modules with their own level still write to the root's appenders
+    for (module, level) in &settings.modules {
+        builder = builder.logger(Logger::builder().build(module, *level));
+    }
+
+    Ok(builder.build(root.build(settings.level))?)
+}
+
+/// Sets up logging as `log4rs.yaml` says, or from `settings` if there is no such file.
+pub fn init(settings: &LoggingSettings) {
+    let config = build_config(settings).expect("Failed to build the log4rs configuration");
+    let handle = log4rs::init_config(config).expect("Failed to initialize log4rs");
+    let _ = LOGGER.set((handle, settings.clone()));
+}
+
+/// Builds the log4rs configuration anew, which reopens every log file. Called after the audit
+/// log was rewritten, so that log4rs writes to the new file and counts its real size towards
+/// rotation. Call it while holding `lock_audit_log`.
+pub fn reopen_log_files() -> Result<(), Error> {
+    // Nothing to reopen when logging isn't set up, e.g. in tests
+    let Some((handle, settings)) = LOGGER.get() else {
+        return Ok(());
+    };
+
+    handle.set_config(build_config(settings)?);
+    Ok(())
+}
+
+/// What an audit entry is about.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditKind {
+    Confession,
+    Reply,
+}
+
+/// What happened to a confession or reply.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    // Submitted by its author, before any checks
+    Received,
+    // Refused by the content filter
+    Filtered,
+    // Waiting in the review queue
+    HeldForReview,
+    // Rejected by a moderator in the review queue
+    Rejected,
+    // Queued to be posted later
+    Scheduled,
+    // Posted in its channel or thread
+    Published,
+    // Removed from the queue after failing to post too often
+    Dropped,
+    // Deleted by its author after it was posted
+    Deleted,
+}
+
+/// One line of the audit log.
+#[derive(Debug, Serialize)]
+pub struct AuditEntry<'a> {
+    timestamp: DateTime<Utc>,
+    kind: AuditKind,
+    outcome: AuditOutcome,
+    guild_id: GuildId,
+    channel_id: Option<ChannelId>,
+    thread_id: Option<ChannelId>,
+    confession_number: Option<i64>,
+    // Written by `log`, encrypted if an audit key is configured
+    #[serde(skip)]
+    author_hash: &'a str,
+    #[serde(skip)]
+    content: &'a str,
+}
+
+/// The fields of an entry that identify the author or tell what they wrote.
+#[derive(Serialize)]
+struct Contents<'a> {
+    author_hash: &'a str,
+    content: &'a str,
+}
+
+#[derive(Serialize)]
+#[serde(untagged)]
+enum Protected<'a> {
+    Plain(Contents<'a>),
+    Sealed { sealed: String },
+}
+
+#[derive(Serialize)]
+struct AuditLine<'a> {
+    #[serde(flatten)]
+    entry: &'a AuditEntry<'a>,
+    #[serde(flatten)]
+    contents: Protected<'a>,
+}
+
+impl<'a> AuditEntry<'a> {
+    /// An entry about a confession.
+    pub fn confession(
+        guild_id: GuildId,
+        author_hash: &'a str,
+        content: &'a str,
+        outcome: AuditOutcome,
+    ) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            kind: AuditKind::Confession,
+            outcome,
+            guild_id,
+            channel_id: None,
+            thread_id: None,
+            confession_number: None,
+            author_hash,
+            content,
+        }
+    }
+
+    /// An entry about an anonymous reply to confession `confession_number`.
+    pub fn reply(
+        guild_id: GuildId,
+        confession_number: i64,
+        author_hash: &'a str,
+        content: &'a str,
+        outcome: AuditOutcome,
+    ) -> Self {
+        Self {
+            kind: AuditKind::Reply,
+            confession_number: Some(confession_number),
+            ..Self::confession(guild_id, author_hash, content, outcome)
+        }
+    }
+
+    /// Sets the number of the confession.
+    pub fn number(self, confession_number: i64) -> Self {
+        Self {
+            confession_number: Some(confession_number),
+            ..self
+        }
+    }
+
+    /// Sets where the confession or reply was posted.
+    pub fn posted_in(self, channel_id: ChannelId, thread_id: Option<ChannelId>) -> Self {
+        Self {
+            channel_id: Some(channel_id),
+            thread_id,
+            ..self
+        }
+    }
+
+    /// Writes the entry to the audit log. With an audit key, the author hash and content are
+    /// sealed into a `sealed` field that only `decrypt-audit` can open.
+    pub fn log(&self, key: Option<&AuditKey>) {
+        let contents = Contents {
+            author_hash: self.author_hash,
+            content: self.content,
+        };
+
+        let contents = match key {
+            Some(key) => match serde_json::to_string(&contents) {
+                Ok(json) => Protected::Sealed {
+                    sealed: key.seal(&json),
+                },
+                Err(e) => {
+                    log::error!("Failed to serialize audit entry: {:?}", e);
+                    return;
+                }
+            },
+            None => Protected::Plain(contents),
+        };
+
+        match serde_json::to_string(&AuditLine {
+            entry: self,
+            contents,
+        }) {
+            Ok(line) => {
+                let _lock = lock_audit_log();
+                log::info!(target: AUDIT_TARGET, "{}", line);
+            }
+            Err(e) => log::error!("Failed to serialize audit entry: {:?}", e),
+        }
+    }
 }
@@ -1,39 +1,38 @@
+mod anonymity;
+mod appearance;
+mod attachment;
+mod audit;
 mod commands;
 mod config;
+mod filter;
 mod logging;
+mod rate_limit;
+mod retention;
+mod sanitize;
+mod schedule;
+mod storage;
 mod utils;
 
-use log::LevelFilter;
-use log4rs::{
-    append::{
-        console::ConsoleAppender,
-        rolling_file::{
-            RollingFileAppender,
-            policy::compound::{
-                CompoundPolicy, roll::fixed_window::FixedWindowRoller, trigger::size::SizeTrigger,
-            },
-        },
-    },
-    config::{Appender, Config as Log4rsConfig, Root},
-    encode::pattern::PatternEncoder,
-};
-use std::{sync::Arc, time::Duration};
+use std::sync::Arc;
 use tokio::sync::RwLock;
 
-use poise::{
-    Modal,
-    serenity_prelude::{self as serenity, CacheHttp, GatewayIntents},
-};
+use poise::serenity_prelude::{self as serenity, GatewayIntents};
 
-use commands::{confess, confessembed, set_confession_thread};
-use config::Config;
-use utils::{CONFESS_BUTTON_ID, ConfessionModal};
+use commands::{
+    board, confess, confessembed, confession, reply, review, rotate_author_secret,
+    set_confession_thread,
+};
+use config::{Config, LoggingSettings};
+use storage::Storage;
+use utils::{BOARD_SELECT_ID, CONFESS_BUTTON_ID};
 
 // --- Poise Types ---
 
 /// User data, which is stored and accessible in all command invocations
+#[derive(Clone)]
 pub struct Data {
     pub config: Arc<RwLock<Config>>,
+    pub storage: Arc<Storage>,
 }
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
@@ -58,45 +57,70 @@ async fn on_error(error: poise::FrameworkError<'_, Data, Error>) {
     }
 }
 
+// --- Event Handler ---
+
+async fn event_handler(
+    ctx: &serenity::Context,
+    event: &serenity::FullEvent,
+    data: &Data,
+) -> Result<(), Error> {
+    // Guilds also go unavailable during outages; their audit entries and stored data are only
+    // purged once the bot has actually left or been removed
+    if let serenity::FullEvent::GuildDelete { incomplete, .. } = event {
+        if !incomplete.unavailable {
+            retention::purge_guild(data, incomplete.id).await;
+        }
+        return Ok(());
+    }
+
+    let serenity::FullEvent::InteractionCreate { interaction } = event else {
+        return Ok(());
+    };
+
+    let Some(component) = interaction.as_message_component() else {
+        return Ok(());
+    };
+
+    if review::is_review_component(&component.data.custom_id) {
+        return review::handle_review_interaction(ctx, data, component).await;
+    }
+
+    if confession::reveal::is_reveal_component(&component.data.custom_id) {
+        return confession::reveal::handle_reveal_interaction(ctx, data, component).await;
+    }
+
+    if reply::is_reply_component(&component.data.custom_id) {
+        return reply::handle_reply_interaction(ctx, data, component).await;
+    }
+
+    if component.data.custom_id == CONFESS_BUTTON_ID {
+        return confess::handle_confess_button(ctx, data, component, None).await;
+    }
+
+    if component.data.custom_id == BOARD_SELECT_ID {
+        let board = board::selected_board(component);
+        return confess::handle_confess_button(ctx, data, component, board).await;
+    }
+
+    Ok(())
+}
+
 // --- Main ---
 
 #[tokio::main]
 async fn main() {
-    // Configure log4rs for file rotation and stdout logging
-    let window_roller = FixedWindowRoller::builder()
-        .base(1)
-        .build("logs/confessions_audit.{}.log", 10)
-        .expect("Failed to build window roller");
-
-    let size_trigger = SizeTrigger::new(10 * 1024 * 1024); // 10MB
-
-    let compound_policy = CompoundPolicy::new(Box::new(size_trigger), Box::new(window_roller));
-
-    let file_appender = RollingFileAppender::builder()
-        .encoder(Box::new(PatternEncoder::new(
-            "[{d(%Y-%m-%dT%H:%M:%S%z)}] | {l} | {M} | {m}\n",
-        )))
-        .build("logs/confessions_audit.log", Box::new(compound_policy))
-        .expect("Failed to build rolling file appender");
-
-    let stdout_appender = ConsoleAppender::builder()
-        .encoder(Box::new(PatternEncoder::new(
-            "[{d(%Y-%m-%dT%H:%M:%S%z)}] | {l} | {M} | {m}\n",
-        )))
-        .build();
-
-    let log_config = Log4rsConfig::builder()
-        .appender(Appender::builder().build("file", Box::new(file_appender)))
-        .appender(Appender::builder().build("stdout", Box::new(stdout_appender)))
-        .build(
-            Root::builder()
-                .appender("file")
-                .appender("stdout")
-                .build(LevelFilter::Warn),
-        )
-        .expect("Failed to build log4rs config");
+    // Only the `logging` section of the configuration is needed before logging is set up; the
+    // rest is loaded and checked afterwards
+    let logging_settings = LoggingSettings::read();
+
+    // Run a command-line subcommand such as `decrypt-audit` instead of the bot if one was given
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = audit::run_cli(&args, &logging_settings.directory) {
+        std::process::exit(code);
+    }
 
-    log4rs::init_config(log_config).expect("Failed to initialize log4rs");
+    // Log to files and stdout as configured, or as `log4rs.yaml` says if present
+    logging::init(&logging_settings);
 
     // Load configuration
     let config = match Config::load().await {
@@ -107,74 +131,69 @@ async fn main() {
         }
     };
 
+    match config.audit_key() {
+        Some(key) => log::warn!("Encrypting audit records with key {}", key.id()),
+        None => {
+            log::warn!("No audit_public_key configured, audit records are stored as plain text")
+        }
+    }
+
+    // Open the database holding everything that must survive restarts
+    let storage = match Storage::open(&config.moderation_audit_secret) {
+        Ok(s) => s,
+        Err(e) => {
+            log::error!("Failed to open database: {}", e);
+            return;
+        }
+    };
+
     let token = config.discord_token.clone();
     let config_arc = Arc::new(RwLock::new(config));
+    let storage_arc = Arc::new(storage);
     let framework = poise::Framework::builder()
         .options(poise::FrameworkOptions {
             commands: vec![
                 set_confession_thread::set_confession_thread(),
                 confessembed::confessembed(),
                 confess::confess(),
+                confession::confession(),
+                review::set_review_channel(),
+                rotate_author_secret::rotate_author_secret(),
             ],
-            event_handler: |ctx, event, _framework, _data| {
-                Box::pin(async move {
-                    if let serenity::FullEvent::InteractionCreate { interaction } = event {
-                        if let Some(component) = interaction.as_message_component() {
-                            if component.data.custom_id == CONFESS_BUTTON_ID {
-                                let custom_id = component.id.to_string();
-                                component
-                                    .create_response(
-                                        ctx.http(),
-                                        ConfessionModal::create(None, custom_id.clone()),
-                                    )
-                                    .await?;
-
-                                let response =
-                                    serenity::collector::ModalInteractionCollector::new(&ctx.shard)
-                                        .filter(move |modal_interaction| {
-                                            modal_interaction.data.custom_id == custom_id
-                                        })
-                                        .timeout(Duration::from_secs(3600))
-                                        .await;
-
-                                if let Some(modal_interaction) = response {
-                                    let data =
-                                        ConfessionModal::parse(modal_interaction.data.clone());
-
-                                    if let Ok(data) = data {
-                                        confess::handle_modal_submission(
-                                            &ctx,
-                                            _data.config.clone(),
-                                            &modal_interaction,
-                                            data,
-                                        )
-                                        .await?;
-                                    }
-                                }
-                            }
-                        }
-                    }
-                    Ok(())
-                })
-            },
+            event_handler: |ctx, event, _framework, data| Box::pin(event_handler(ctx, event, data)),
             prefix_options: poise::PrefixFrameworkOptions {
                 prefix: Some("~".into()),
                 ..Default::default()
             },
             on_error: |error| Box::pin(on_error(error)),
+            // Never ping anyone from replies; raw messages set this themselves
+            allowed_mentions: Some(sanitize::no_mentions()),
             ..Default::default()
         })
         .setup(move |ctx, _ready, framework| {
             Box::pin(async move {
                 log::info!("Registering commands globally...");
                 poise::builtins::register_globally(ctx, &framework.options().commands).await?;
-                Ok(Data { config: config_arc })
+                let data = Data {
+                    config: config_arc,
+                    storage: storage_arc,
+                };
+
+                // Post queued confessions in the background, including those left over from
+                // before a restart
+                tokio::spawn(schedule::run(ctx.http.clone(), data.clone()));
+
+                // Purge audit entries past their retention period
+                tokio::spawn(retention::run(data.clone()));
+
+                Ok(data)
             })
         })
         .build();
 
-    // We need Guilds, MessageContent, and GuildMessages for command registration and interaction handling
-    let intents = GatewayIntents::empty(); //serenity::GatewayIntents::non_privileged();
+    // Interactions don't need any intents. Guilds lets the bot know when it leaves a guild, so
+    // that the guild's audit entries and stored data can be purged.
+    let intents = GatewayIntents::GUILDS;
 
     let client = serenity::ClientBuilder::new(token, intents)
         .framework(framework)
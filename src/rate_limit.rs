@@ -0,0 +1,239 @@
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::RateLimits;
+
+/// Which limit a submission ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitKind {
+    UserCooldown,
+    UserDailyLimit,
+    GuildBurst,
+}
+
+/// A submission that has to wait until `retry_at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RateLimited {
+    pub kind: LimitKind,
+    pub retry_at: DateTime<Utc>,
+}
+
+impl RateLimited {
+    /// A friendly explanation for the user, including when they may try again.
+    pub fn message(&self, limits: &RateLimits) -> String {
+        let retry = format!("<t:{}:R>", self.retry_at.timestamp());
+
+        match self.kind {
+            LimitKind::UserCooldown => {
+                format!("You can submit another confession {}.", retry)
+            }
+            LimitKind::UserDailyLimit => format!(
+                "You have reached the limit of {} confessions per day. You can submit another one {}.",
+                limits.user_daily_limit, retry
+            ),
+            LimitKind::GuildBurst => format!(
+                "This server is receiving a lot of confessions right now. Please try again {}.",
+                retry
+            ),
+        }
+    }
+}
+
+/// Checks the limits against the times of earlier submissions and returns the limit that
+/// blocks a new submission at `now`, picking the one that lasts longest.
+pub fn check(
+    limits: &RateLimits,
+    user_submissions: &[DateTime<Utc>],
+    guild_submissions: &[DateTime<Utc>],
+    now: DateTime<Utc>,
+) -> Option<RateLimited> {
+    let mut blocked: Vec<RateLimited> = Vec::new();
+
+    if limits.user_cooldown_minutes > 0 {
+        let cooldown = Duration::minutes(limits.user_cooldown_minutes.into());
+
+        if let Some(last) = user_submissions.iter().max() {
+            let retry_at = *last + cooldown;
+
+            if retry_at > now {
+                blocked.push(RateLimited {
+                    kind: LimitKind::UserCooldown,
+                    retry_at,
+                });
+            }
+        }
+    }
+
+    if limits.user_daily_limit > 0 {
+        let day = Duration::days(1);
+
+        if let Some(retry_at) = window_retry_at(user_submissions, limits.user_daily_limit, day, now)
+        {
+            blocked.push(RateLimited {
+                kind: LimitKind::UserDailyLimit,
+                retry_at,
+            });
+        }
+    }
+
+    if limits.guild_burst_limit > 0 && limits.guild_burst_window_minutes > 0 {
+        let window = Duration::minutes(limits.guild_burst_window_minutes.into());
+
+        if let Some(retry_at) =
+            window_retry_at(guild_submissions, limits.guild_burst_limit, window, now)
+        {
+            blocked.push(RateLimited {
+                kind: LimitKind::GuildBurst,
+                retry_at,
+            });
+        }
+    }
+
+    blocked.into_iter().max_by_key(|limited| limited.retry_at)
+}
+
+/// For a sliding window allowing `limit` submissions, returns when the next one is allowed
+/// if the window is currently full.
+fn window_retry_at(
+    submissions: &[DateTime<Utc>],
+    limit: u32,
+    window: Duration,
+    now: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    let mut recent: Vec<DateTime<Utc>> = submissions
+        .iter()
+        .copied()
+        .filter(|at| *at > now - window)
+        .collect();
+
+    if recent.len() < limit as usize {
+        return None;
+    }
+
+    // The window frees up once enough of the recent submissions have aged out of it
+    recent.sort();
+    Some(recent[recent.len() - limit as usize] + window)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(cooldown: u32, daily: u32, burst: u32, burst_window: u32) -> RateLimits {
+        RateLimits {
+            user_cooldown_minutes: cooldown,
+            user_daily_limit: daily,
+            guild_burst_limit: burst,
+            guild_burst_window_minutes: burst_window,
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        DateTime::from_timestamp(1_800_000_000, 0).unwrap()
+    }
+
+    fn minutes_ago(minutes: &[i64]) -> Vec<DateTime<Utc>> {
+        minutes
+            .iter()
+            .map(|minutes| now() - Duration::minutes(*minutes))
+            .collect()
+    }
+
+    #[test]
+    fn no_limits_allow_everything() {
+        let submissions = minutes_ago(&[0, 0, 0, 1, 2]);
+        assert_eq!(
+            check(&limits(0, 0, 0, 0), &submissions, &submissions, now()),
+            None
+        );
+    }
+
+    #[test]
+    fn cooldown_runs_from_the_last_submission() {
+        let limits = limits(10, 0, 0, 0);
+
+        assert_eq!(
+            check(&limits, &minutes_ago(&[30, 4]), &[], now()),
+            Some(RateLimited {
+                kind: LimitKind::UserCooldown,
+                retry_at: now() + Duration::minutes(6),
+            })
+        );
+        assert_eq!(check(&limits, &minutes_ago(&[10]), &[], now()), None);
+        assert_eq!(check(&limits, &[], &[], now()), None);
+    }
+
+    #[test]
+    fn daily_limit_rolls_over_as_submissions_age_out() {
+        let limits = limits(0, 3, 0, 0);
+        let submissions = minutes_ago(&[23 * 60, 60, 5]);
+
+        // The window is full until the oldest of the three is a day old
+        assert_eq!(
+            check(&limits, &submissions, &[], now()),
+            Some(RateLimited {
+                kind: LimitKind::UserDailyLimit,
+                retry_at: now() + Duration::minutes(60),
+            })
+        );
+        assert_eq!(
+            check(&limits, &submissions, &[], now() + Duration::minutes(60)),
+            None
+        );
+
+        // Submissions older than a day don't count
+        assert_eq!(
+            check(&limits, &minutes_ago(&[25 * 60, 60, 5]), &[], now()),
+            None
+        );
+    }
+
+    #[test]
+    fn daily_limit_waits_for_enough_submissions_to_age_out() {
+        let limits = limits(0, 2, 0, 0);
+        let submissions = minutes_ago(&[23 * 60, 22 * 60, 60, 5]);
+
+        // Four submissions against a limit of two: the third newest has to age out
+        assert_eq!(
+            check(&limits, &submissions, &[], now()).map(|limited| limited.retry_at),
+            Some(now() + Duration::minutes(23 * 60))
+        );
+    }
+
+    #[test]
+    fn guild_burst_limit_counts_the_whole_guild() {
+        let limits = limits(0, 0, 3, 10);
+
+        assert_eq!(
+            check(&limits, &[], &minutes_ago(&[8, 5, 1]), now()),
+            Some(RateLimited {
+                kind: LimitKind::GuildBurst,
+                retry_at: now() + Duration::minutes(2),
+            })
+        );
+        assert_eq!(check(&limits, &[], &minutes_ago(&[12, 5, 1]), now()), None);
+        assert_eq!(check(&limits, &[], &minutes_ago(&[5, 1]), now()), None);
+    }
+
+    #[test]
+    fn guild_burst_limit_needs_a_window() {
+        let submissions = minutes_ago(&[0, 0, 0]);
+        assert_eq!(check(&limits(0, 0, 1, 0), &[], &submissions, now()), None);
+        assert_eq!(check(&limits(0, 0, 0, 10), &[], &submissions, now()), None);
+    }
+
+    #[test]
+    fn longest_lasting_limit_wins() {
+        let limits = limits(10, 2, 2, 5);
+        let user = minutes_ago(&[120, 3]);
+        let guild = minutes_ago(&[4, 3]);
+
+        // Cooldown ends in 7 minutes, the daily limit in 22 hours, the burst in 1 minute
+        assert_eq!(
+            check(&limits, &user, &guild, now()),
+            Some(RateLimited {
+                kind: LimitKind::UserDailyLimit,
+                retry_at: now() + Duration::minutes(22 * 60),
+            })
+        );
+    }
+}
@@ -0,0 +1,368 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude::GuildId;
+use serde_json::Value;
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    Data, Error,
+    audit::audit_log_files,
+    config::RetentionAction,
+    logging::{lock_audit_log, reopen_log_files},
+    storage::Storage,
+};
+
+// How often audit entries past the retention period are purged
+const PURGE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
+
+// Fields of an audit entry that identify the author or tell what they wrote
+const REDACTED_FIELDS: [&str; 3] = ["author_hash", "content", "sealed"];
+
+/// Which audit entries to purge and how.
+#[derive(Debug, Clone, Copy)]
+pub struct Purge {
+    // Only entries of this guild, or of every guild when `None`
+    pub guild_id: Option<GuildId>,
+    // Only entries written before this time, or all of them when `None`
+    pub before: Option<DateTime<Utc>>,
+    pub action: RetentionAction,
+}
+
+/// How many audit entries a purge removed or redacted.
+#[derive(Debug, Default)]
+pub struct PurgeStats {
+    pub deleted: usize,
+    pub redacted: usize,
+}
+
+enum LineAction {
+    Keep,
+    Delete,
+    Replace(String),
+}
+
+/// Reads the timestamp of a line written before the audit log switched to JSON.
+fn legacy_timestamp(line: &str) -> Option<DateTime<Utc>> {
+    let (timestamp, _) = line.strip_prefix('[')?.split_once(']')?;
+    DateTime::parse_from_str(timestamp, "%Y-%m-%dT%H:%M:%S%z")
+        .ok()
+        .map(|timestamp| timestamp.to_utc())
+}
+
+impl Purge {
+    fn expired(&self, timestamp: Option<DateTime<Utc>>) -> bool {
+        match self.before {
+            None => true,
+            Some(before) => timestamp.is_some_and(|timestamp| timestamp < before),
+        }
+    }
+
+    fn apply(&self, line: &str) -> LineAction {
+        let Ok(Value::Object(mut entry)) = serde_json::from_str::<Value>(line) else {
+            // Lines from before the audit log switched to JSON can't be attributed to a guild
+            // or redacted, so they are only ever deleted for being too old
+            return match self.guild_id {
+                None if self.before.is_some() && self.expired(legacy_timestamp(line)) => {
+                    LineAction::Delete
+                }
+                _ => LineAction::Keep,
+            };
+        };
+
+        let in_guild = self.guild_id.is_none_or(|guild_id| {
+            entry.get("guild_id").and_then(Value::as_str) == Some(guild_id.to_string().as_str())
+        });
+        let timestamp = entry
+            .get("timestamp")
+            .and_then(Value::as_str)
+            .and_then(|timestamp| DateTime::parse_from_rfc3339(timestamp).ok())
+            .map(|timestamp| timestamp.to_utc());
+
+        if !in_guild || !self.expired(timestamp) {
+            return LineAction::Keep;
+        }
+
+        match self.action {
+            RetentionAction::Delete => LineAction::Delete,
+            RetentionAction::Redact => {
+                if !REDACTED_FIELDS
+                    .iter()
+                    .any(|field| entry.contains_key(*field))
+                {
+                    return LineAction::Keep;
+                }
+
+                for field in REDACTED_FIELDS {
+                    entry.remove(field);
+                }
+                entry.insert("redacted".to_string(), Value::Bool(true));
+                LineAction::Replace(Value::Object(entry).to_string())
+            }
+        }
+    }
+}
+
+/// Deletes or redacts audit entries in the audit log in `directory` and its rotated files. Each
+/// changed file is replaced by a purged copy while no new entries can be written, and log4rs
+/// then reopens its files, so entries logged meanwhile aren't lost.
+pub fn purge(directory: &Path, purge: Purge) -> Result<PurgeStats, Error> {
+    let _lock = lock_audit_log();
+    let mut stats = PurgeStats::default();
+    let mut replaced = false;
+
+    let files = match audit_log_files(directory) {
+        Ok(files) => files,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(stats),
+        Err(e) => return Err(e.into()),
+    };
+
+    for path in files {
+        let content = fs::read_to_string(&path)?;
+        let mut purged = String::with_capacity(content.len());
+        let mut changed = false;
+
+        for line in content.lines() {
+            match purge.apply(line) {
+                LineAction::Keep => {
+                    purged.push_str(line);
+                    purged.push('\n');
+                }
+                LineAction::Delete => {
+                    stats.deleted += 1;
+                    changed = true;
+                }
+                LineAction::Replace(redacted) => {
+                    purged.push_str(&redacted);
+                    purged.push('\n');
+                    stats.redacted += 1;
+                    changed = true;
+                }
+            }
+        }
+
+        if changed {
+            let temporary = path.with_extension("purge");
+            fs::write(&temporary, purged)?;
+            fs::rename(&temporary, &path)?;
+            replaced = true;
+        }
+    }
+
+    // log4rs still has the replaced files open
+    if replaced {
+        reopen_log_files()?;
+    }
+
+    Ok(stats)
+}
+
+/// Runs a purge of the audit log and of the moderation audit in `confessions.db` on a blocking
+/// thread, since it reads and rewrites every audit file.
+pub async fn purge_in_background(
+    directory: PathBuf,
+    storage: Arc<Storage>,
+    options: Purge,
+) -> Result<PurgeStats, Error> {
+    tokio::task::spawn_blocking(move || -> Result<PurgeStats, Error> {
+        // Both purges run even if the other fails
+        let files = purge(&directory, options);
+        let database =
+            storage.purge_moderation_audit(options.guild_id, options.before, options.action);
+
+        match (files, database) {
+            (Ok(mut stats), Ok(purged)) => {
+                match options.action {
+                    RetentionAction::Delete => stats.deleted += purged,
+                    RetentionAction::Redact => stats.redacted += purged,
+                }
+                Ok(stats)
+            }
+            (Err(e), Ok(_)) => Err(format!("audit log: {}", e).into()),
+            (Ok(_), Err(e)) => Err(format!("moderation audit: {}", e).into()),
+            (Err(files), Err(database)) => {
+                Err(format!("audit log: {}; moderation audit: {}", files, database).into())
+            }
+        }
+    })
+    .await?
+}
+
+/// Removes every audit entry and everything stored in `confessions.db` about a guild the bot
+/// has left.
+pub async fn purge_guild(data: &Data, guild_id: GuildId) {
+    let directory = data.config.read().await.logging.directory.clone();
+    let result = purge_in_background(
+        directory,
+        data.storage.clone(),
+        Purge {
+            guild_id: Some(guild_id),
+            before: None,
+            action: RetentionAction::Delete,
+        },
+    )
+    .await;
+
+    match result {
+        Ok(stats) => log::warn!(
+            "Left guild {}, deleted its {} audit entries",
+            guild_id,
+            stats.deleted
+        ),
+        Err(e) => log::error!(
+            "Failed to purge the audit entries of guild {}: {:?}",
+            guild_id,
+            e
+        ),
+    }
+
+    match data.storage.delete_guild_data(guild_id) {
+        Ok(deleted) => log::warn!(
+            "Left guild {}, deleted its {} stored confessions, replies, queued confessions and bans",
+            guild_id,
+            deleted
+        ),
+        Err(e) => log::error!(
+            "Failed to delete the stored data of guild {}: {:?}",
+            guild_id,
+            e
+        ),
+    }
+}
+
+/// Purges audit entries once they are past the configured retention period. Runs for as long
+/// as the bot does.
+pub async fn run(data: Data) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let (retention_days, action, directory) = {
+            let config = data.config.read().await;
+            (
+                config.audit_retention_days,
+                config.audit_retention_action,
+                config.logging.directory.clone(),
+            )
+        };
+
+        // Entries are kept forever unless a retention period is configured
+        if retention_days == 0 {
+            continue;
+        }
+
+        let result = purge_in_background(
+            directory,
+            data.storage.clone(),
+            Purge {
+                guild_id: None,
+                before: Some(Utc::now() - Duration::days(retention_days.into())),
+                action,
+            },
+        )
+        .await;
+
+        match result {
+            Ok(stats) if stats.deleted + stats.redacted > 0 => log::info!(
+                "Audit retention deleted {} and redacted {} entries",
+                stats.deleted,
+                stats.redacted
+            ),
+            Ok(_) => {}
+            Err(e) => log::error!("Failed to purge expired audit entries: {:?}", e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(guild_id: u64, timestamp: &str) -> String {
+        format!(
+            r#"{{"timestamp":"{}","kind":"confession","outcome":"received","guild_id":"{}","channel_id":null,"thread_id":null,"confession_number":null,"author_hash":"hash","content":"text"}}"#,
+            timestamp, guild_id
+        )
+    }
+
+    fn directory(name: &str) -> PathBuf {
+        let directory =
+            std::env::temp_dir().join(format!("retention_{}_{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&directory);
+        fs::create_dir_all(&directory).unwrap();
+        directory
+    }
+
+    #[test]
+    fn purging_a_guild_replaces_the_files() {
+        let directory = directory("guild");
+        let current = directory.join("confessions_audit.log");
+        let rotated = directory.join("confessions_audit.1.log");
+        fs::write(
+            &current,
+            format!(
+                "{}\n{}\n",
+                line(1, "2026-01-02T00:00:00Z"),
+                line(2, "2026-01-02T00:00:00Z")
+            ),
+        )
+        .unwrap();
+        fs::write(&rotated, format!("{}\n", line(2, "2026-01-01T00:00:00Z"))).unwrap();
+
+        let stats = purge(
+            &directory,
+            Purge {
+                guild_id: Some(GuildId::new(1)),
+                before: None,
+                action: RetentionAction::Delete,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(stats.deleted, 1);
+        assert_eq!(
+            fs::read_to_string(&current).unwrap(),
+            format!("{}\n", line(2, "2026-01-02T00:00:00Z"))
+        );
+        assert_eq!(
+            fs::read_to_string(&rotated).unwrap(),
+            format!("{}\n", line(2, "2026-01-01T00:00:00Z"))
+        );
+        assert_eq!(fs::read_dir(&directory).unwrap().count(), 2);
+    }
+
+    #[test]
+    fn redacting_keeps_expired_entries_without_their_contents() {
+        let directory = directory("redact");
+        let current = directory.join("confessions_audit.log");
+        fs::write(
+            &current,
+            format!(
+                "{}\n{}\n",
+                line(1, "2026-01-01T00:00:00Z"),
+                line(1, "2026-03-01T00:00:00Z")
+            ),
+        )
+        .unwrap();
+
+        let stats = purge(
+            &directory,
+            Purge {
+                guild_id: None,
+                before: Some("2026-02-01T00:00:00Z".parse().unwrap()),
+                action: RetentionAction::Redact,
+            },
+        )
+        .unwrap();
+
+        assert_eq!(stats.redacted, 1);
+        let content = fs::read_to_string(&current).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        assert!(lines[0].contains(r#""redacted":true"#));
+        assert!(!lines[0].contains("hash") && !lines[0].contains("text"));
+        assert_eq!(lines[1], line(1, "2026-03-01T00:00:00Z"));
+    }
+}
@@ -0,0 +1,121 @@
+use poise::serenity_prelude::CreateAllowedMentions;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+use std::sync::LazyLock;
+
+// Breaks up @everyone/@here without changing how the text reads
+const ZERO_WIDTH_SPACE: char = '\u{200B}';
+
+/// How mentions in confessions are neutralised before they are posted.
+#[derive(
+    Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default, poise::ChoiceParameter,
+)]
+pub enum MentionMode {
+    /// Show the mention as plain text.
+    #[default]
+    Escape,
+    /// Remove the mention.
+    Strip,
+}
+
+/// Mentions Discord renders or pings: `<@id>`, `<@!id>`, `<@&id>`, `<#id>`, `</command:id>`,
+/// `<id:browse>` style guild navigation, `@everyone` and `@here`. The latter two only count on
+/// their own, not inside an email address or a longer word.
+static MENTION_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"<(?:@[!&]?\d+|#\d+|/[\w\- ]+:\d+|id:\w+)>|\B@(?:everyone|here)\b")
+        .expect("Invalid mention regex")
+});
+
+/// Neutralises every mention in the text, so posting it can neither ping nor point at anyone.
+pub fn sanitize_mentions(text: &str, mode: MentionMode) -> String {
+    let sanitized = MENTION_REGEX.replace_all(text, |captures: &Captures| {
+        let mention = &captures[0];
+
+        match mode {
+            MentionMode::Strip => String::new(),
+            // A backslash stops Discord from formatting `<...>` mentions
+            MentionMode::Escape if mention.starts_with('<') => format!("\\{}", mention),
+            MentionMode::Escape => mention.replacen('@', &format!("@{}", ZERO_WIDTH_SPACE), 1),
+        }
+    });
+
+    sanitized.trim().to_string()
+}
+
+/// Allowed mentions for everything the bot sends: nobody gets pinged.
+pub fn no_mentions() -> CreateAllowedMentions {
+    CreateAllowedMentions::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn escape(text: &str) -> String {
+        sanitize_mentions(text, MentionMode::Escape)
+    }
+
+    fn strip(text: &str) -> String {
+        sanitize_mentions(text, MentionMode::Strip)
+    }
+
+    #[test]
+    fn user_mentions() {
+        assert_eq!(escape("hi <@123>"), "hi \\<@123>");
+        assert_eq!(strip("hi <@123>"), "hi");
+        assert_eq!(escape("hi <@!123>"), "hi \\<@!123>");
+        assert_eq!(strip("hi <@!123> there"), "hi  there");
+    }
+
+    #[test]
+    fn role_mentions() {
+        assert_eq!(escape("<@&456> team"), "\\<@&456> team");
+        assert_eq!(strip("<@&456> team"), "team");
+    }
+
+    #[test]
+    fn channel_mentions() {
+        assert_eq!(escape("see <#789>"), "see \\<#789>");
+        assert_eq!(strip("see <#789>"), "see");
+    }
+
+    #[test]
+    fn command_mentions() {
+        assert_eq!(escape("use </confess:42>"), "use \\</confess:42>");
+        assert_eq!(
+            escape("use </confession delete:42>"),
+            "use \\</confession delete:42>"
+        );
+        assert_eq!(strip("use </confession delete:42> now"), "use  now");
+    }
+
+    #[test]
+    fn guild_navigation_mentions() {
+        assert_eq!(escape("go to <id:customize>"), "go to \\<id:customize>");
+        assert_eq!(strip("go to <id:customize>"), "go to");
+    }
+
+    #[test]
+    fn mass_mentions() {
+        assert_eq!(escape("@everyone look"), "@\u{200B}everyone look");
+        assert_eq!(escape("hey @here!"), "hey @\u{200B}here!");
+        assert_eq!(strip("@everyone look"), "look");
+        assert_eq!(strip("hey @here!"), "hey !");
+    }
+
+    #[test]
+    fn near_misses_stay_untouched() {
+        for text in [
+            "mail me@here.com",
+            "@everyoneelse",
+            "@hereafter",
+            "<@abc>",
+            "<#general>",
+            "a < b and c > d",
+            "user@example.com",
+        ] {
+            assert_eq!(escape(text), text);
+            assert_eq!(strip(text), text);
+        }
+    }
+}
@@ -0,0 +1,126 @@
+use std::sync::Arc;
+
+use chrono::{DateTime, Days, Duration, Utc};
+use poise::serenity_prelude as serenity;
+
+use crate::{
+    Data,
+    commands::confess::publish_confession,
+    config::{ScheduleMode, ScheduleSettings},
+    logging::{AuditEntry, AuditOutcome},
+    storage::ScheduledConfession,
+};
+
+// How often the queue is checked for confessions that are due
+const POLL_INTERVAL: std::time::Duration = std::time::Duration::from_secs(30);
+
+// Confessions that could not be posted are tried again after this long...
+const RETRY_DELAY_MINUTES: i64 = 5;
+
+// ...and dropped after this many failed attempts
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Returns when a confession submitted at `now` should be posted, or `None` to post it right
+/// away. Random delays are picked uniformly between the two bounds; batches go out at the
+/// first batch time at least `min_delay_minutes` away.
+pub fn post_time(settings: &ScheduleSettings, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    let min_delay = i64::from(settings.min_delay_minutes.min(settings.max_delay_minutes)) * 60;
+    let max_delay = i64::from(settings.max_delay_minutes) * 60;
+
+    match settings.mode {
+        ScheduleMode::Immediate => None,
+        ScheduleMode::RandomDelay => {
+            Some(now + Duration::seconds(rand::random_range(min_delay..=max_delay)))
+        }
+        ScheduleMode::Batches => {
+            let earliest = now + Duration::seconds(min_delay);
+
+            // Every batch time comes around within a day of the earliest posting time
+            [earliest.date_naive(), earliest.date_naive() + Days::new(1)]
+                .into_iter()
+                .flat_map(|date| {
+                    settings
+                        .batch_times
+                        .iter()
+                        .map(move |time| date.and_time(*time).and_utc())
+                })
+                .filter(|batch| *batch >= earliest)
+                .min()
+        }
+    }
+}
+
+/// Posts queued confessions once their time has come. Runs for as long as the bot does.
+pub async fn run(http: Arc<serenity::Http>, data: Data) {
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+
+    loop {
+        interval.tick().await;
+
+        let due = match data.storage.due_scheduled(Utc::now()) {
+            Ok(due) => due,
+            Err(e) => {
+                log::error!("Failed to load scheduled confessions: {:?}", e);
+                continue;
+            }
+        };
+
+        for scheduled in due {
+            post_scheduled(&http, &data, scheduled).await;
+        }
+    }
+}
+
+async fn post_scheduled(http: &serenity::Http, data: &Data, scheduled: ScheduledConfession) {
+    let result = publish_confession(
+        scheduled.guild_id,
+        data,
+        http,
+        &scheduled.author_hash,
+        scheduled.draft.clone(),
+        scheduled.created_at,
+    )
+    .await;
+
+    let stored = match result {
+        Ok(_) => data.storage.delete_scheduled(scheduled.id),
+        Err(reason) if scheduled.attempts + 1 >= MAX_ATTEMPTS => {
+            log::error!(
+                "Dropping scheduled confession {} of guild {} after {} failed attempts: {}",
+                scheduled.id,
+                scheduled.guild_id,
+                MAX_ATTEMPTS,
+                reason
+            );
+            let audit_key = data.config.read().await.audit_key();
+            AuditEntry::confession(
+                scheduled.guild_id,
+                &scheduled.author_hash,
+                &scheduled.draft.content,
+                AuditOutcome::Dropped,
+            )
+            .log(audit_key.as_ref());
+            data.storage.delete_scheduled(scheduled.id)
+        }
+        Err(reason) => {
+            log::warn!(
+                "Failed to post scheduled confession {} of guild {}, retrying later: {}",
+                scheduled.id,
+                scheduled.guild_id,
+                reason
+            );
+            data.storage.retry_scheduled(
+                scheduled.id,
+                Utc::now() + Duration::minutes(RETRY_DELAY_MINUTES),
+            )
+        }
+    };
+
+    if let Err(e) = stored {
+        log::error!(
+            "Failed to update scheduled confession {}: {:?}",
+            scheduled.id,
+            e
+        );
+    }
+}
@@ -0,0 +1,1768 @@
+use std::{
+    collections::HashMap,
+    fmt, io,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use poise::serenity_prelude::{ChannelId, ForumTagId, GuildId, MessageId, UserId};
+use rusqlite::{Connection, OptionalExtension, Row, params, params_from_iter, types::Value};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::sync::{Mutex as AsyncMutex, OwnedMutexGuard};
+
+use crate::{attachment::ConfessionImage, config::RetentionAction, utils::truncate};
+
+const DATABASE_FILE: &str = "confessions.db";
+
+// Holds the end of the moderation audit chain outside the database, so that deleting its newest
+// entries is noticed as well
+const AUDIT_HEAD_FILE: &str = "moderation_audit_head.json";
+
+type HmacSha256 = Hmac<Sha256>;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS pending_confessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    author_hash TEXT NOT NULL,
+    content TEXT NOT NULL,
+    interaction_token TEXT NOT NULL,
+    forum_tag INTEGER,
+    board TEXT,
+    content_warning TEXT,
+    title TEXT,
+    signature TEXT,
+    image BLOB,
+    image_name TEXT,
+    claimed INTEGER NOT NULL DEFAULT 0,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS confessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    number INTEGER NOT NULL,
+    author_hash TEXT NOT NULL,
+    content TEXT NOT NULL,
+    channel_id INTEGER NOT NULL,
+    thread_id INTEGER,
+    message_id INTEGER,
+    created_at INTEGER NOT NULL,
+    posted_at INTEGER,
+    deleted_at INTEGER,
+    board TEXT,
+    part_message_ids TEXT,
+    UNIQUE (guild_id, number)
+);
+
+CREATE TABLE IF NOT EXISTS submissions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    author_hash TEXT NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS submissions_by_guild ON submissions (guild_id, created_at);
+
+CREATE TABLE IF NOT EXISTS reveal_requests (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    confession_number INTEGER NOT NULL,
+    requested_by INTEGER NOT NULL,
+    approved_by INTEGER,
+    status TEXT NOT NULL DEFAULT 'pending',
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS bans (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    author_hash TEXT NOT NULL,
+    confession_number INTEGER NOT NULL,
+    reason TEXT,
+    banned_by INTEGER NOT NULL,
+    created_at INTEGER NOT NULL,
+    expires_at INTEGER
+);
+
+CREATE TABLE IF NOT EXISTS reply_aliases (
+    guild_id INTEGER NOT NULL,
+    confession_number INTEGER NOT NULL,
+    author_hash TEXT NOT NULL,
+    alias INTEGER NOT NULL,
+    UNIQUE (guild_id, confession_number, author_hash)
+);
+
+CREATE TABLE IF NOT EXISTS replies (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    confession_number INTEGER NOT NULL,
+    author_hash TEXT NOT NULL,
+    content TEXT NOT NULL,
+    thread_id INTEGER NOT NULL,
+    message_id INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS scheduled_confessions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    author_hash TEXT NOT NULL,
+    content TEXT NOT NULL,
+    forum_tag INTEGER,
+    board TEXT,
+    content_warning TEXT,
+    title TEXT,
+    signature TEXT,
+    image BLOB,
+    image_name TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    post_at INTEGER NOT NULL,
+    created_at INTEGER NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS scheduled_by_time ON scheduled_confessions (post_at);
+
+CREATE TABLE IF NOT EXISTS moderation_audit (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    guild_id INTEGER NOT NULL,
+    action TEXT NOT NULL,
+    actor_id INTEGER NOT NULL,
+    confession_number INTEGER,
+    details TEXT NOT NULL,
+    created_at INTEGER NOT NULL,
+    prev_hash TEXT NOT NULL,
+    hash TEXT NOT NULL
+);
+";
+
+// Columns added after their table was first released, as (table, column, definition)
+const ADDED_COLUMNS: &[(&str, &str, &str)] = &[
+    ("confessions", "deleted_at", "INTEGER"),
+    ("pending_confessions", "forum_tag", "INTEGER"),
+    ("pending_confessions", "board", "TEXT"),
+    ("pending_confessions", "content_warning", "TEXT"),
+    ("pending_confessions", "image", "BLOB"),
+    ("pending_confessions", "image_name", "TEXT"),
+    ("confessions", "board", "TEXT"),
+    ("pending_confessions", "title", "TEXT"),
+    ("pending_confessions", "signature", "TEXT"),
+    ("confessions", "part_message_ids", "TEXT"),
+];
+
+// Characters of a posted confession kept in the `content` column, enough to recognise it in
+// `/confession delete` and reveal requests. The full text is only in the posted message and
+// the audit log.
+const CONFESSION_PREVIEW_CHARS: usize = 80;
+
+// Hash the first entry of the moderation audit chain links to
+const AUDIT_GENESIS_HASH: &str = "genesis";
+
+// Replaces the details of moderation audit entries redacted by the audit retention
+const REDACTED_AUDIT_DETAILS: &str = "redacted";
+
+// Tables holding a guild's confessions, replies, queues and bans, emptied when the bot leaves it
+const GUILD_TABLES: [&str; 8] = [
+    "pending_confessions",
+    "confessions",
+    "submissions",
+    "reveal_requests",
+    "bans",
+    "reply_aliases",
+    "replies",
+    "scheduled_confessions",
+];
+
+/// A confession as its author submitted it, before it is numbered and posted.
+#[derive(Debug, Clone, Default)]
+pub struct Draft {
+    pub content: String,
+    // Category the author picked in a Forum confession channel
+    pub forum_tag: Option<ForumTagId>,
+    // Named board the confession is for (`None` for the guild's default board)
+    pub board: Option<String>,
+    // Shown instead of the confession until readers reveal the spoiler
+    pub content_warning: Option<String>,
+    // Name of the thread or forum post, chosen by the author
+    pub title: Option<String>,
+    // Pseudonym the author signed the confession with
+    pub signature: Option<String>,
+    // Image attached with `/confess`, already stripped of its metadata
+    pub image: Option<ConfessionImage>,
+}
+
+/// A confession waiting for moderator approval in the guild's review channel.
+#[derive(Debug, Clone)]
+pub struct PendingConfession {
+    pub id: i64,
+    pub guild_id: GuildId,
+    pub author_hash: String,
+    pub draft: Draft,
+    // Token of the submitter's interaction, used to notify them if the confession is rejected
+    pub interaction_token: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Draft {
+    /// Reads the draft columns shared by the pending and scheduled confession tables.
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            content: row.get("content")?,
+            forum_tag: row
+                .get::<_, Option<i64>>("forum_tag")?
+                .map(|id| ForumTagId::new(id as u64)),
+            board: row.get("board")?,
+            content_warning: row.get("content_warning")?,
+            title: row.get("title")?,
+            signature: row.get("signature")?,
+            image: match (row.get("image_name")?, row.get("image")?) {
+                (Some(filename), Some(data)) => Some(ConfessionImage { filename, data }),
+                _ => None,
+            },
+        })
+    }
+}
+
+impl PendingConfession {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: GuildId::new(row.get::<_, i64>("guild_id")? as u64),
+            author_hash: row.get("author_hash")?,
+            draft: Draft::from_row(row)?,
+            interaction_token: row.get("interaction_token")?,
+            created_at: timestamp(row.get("created_at")?),
+        })
+    }
+}
+
+/// A confession waiting in the queue until its posting time.
+#[derive(Debug, Clone)]
+pub struct ScheduledConfession {
+    pub id: i64,
+    pub guild_id: GuildId,
+    pub author_hash: String,
+    pub draft: Draft,
+    // Failed attempts to post it so far
+    pub attempts: u32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ScheduledConfession {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            id: row.get("id")?,
+            guild_id: GuildId::new(row.get::<_, i64>("guild_id")? as u64),
+            author_hash: row.get("author_hash")?,
+            draft: Draft::from_row(row)?,
+            attempts: row.get("attempts")?,
+            created_at: timestamp(row.get("created_at")?),
+        })
+    }
+}
+
+/// Number and row id handed out for a confession that is about to be posted.
+#[derive(Debug, Clone, Copy)]
+pub struct ReservedConfession {
+    pub id: i64,
+    pub number: i64,
+}
+
+/// A posted confession.
+#[derive(Debug, Clone)]
+pub struct Confession {
+    pub number: i64,
+    pub author_hash: String,
+    // The start of the confession, see `CONFESSION_PREVIEW_CHARS`
+    pub preview: String,
+    pub channel_id: ChannelId,
+    // `None` when the confession was posted without a thread
+    pub thread_id: Option<ChannelId>,
+    pub message_id: Option<MessageId>,
+    // Messages holding the second and following parts of a long confession
+    pub part_message_ids: Vec<MessageId>,
+    // Named board it was posted to (`None` for the guild's default board)
+    pub board: Option<String>,
+    pub created_at: DateTime<Utc>,
+    // Set once the author has deleted the confession
+    pub deleted: bool,
+}
+
+impl Confession {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            number: row.get("number")?,
+            author_hash: row.get("author_hash")?,
+            preview: row.get("content")?,
+            channel_id: ChannelId::new(row.get::<_, i64>("channel_id")? as u64),
+            thread_id: row
+                .get::<_, Option<i64>>("thread_id")?
+                .map(|id| ChannelId::new(id as u64)),
+            message_id: row
+                .get::<_, Option<i64>>("message_id")?
+                .map(|id| MessageId::new(id as u64)),
+            part_message_ids: row
+                .get::<_, Option<String>>("part_message_ids")?
+                .unwrap_or_default()
+                .split_whitespace()
+                .filter_map(|id| id.parse().ok().map(MessageId::new))
+                .collect(),
+            board: row.get("board")?,
+            created_at: timestamp(row.get("created_at")?),
+            deleted: row.get::<_, Option<i64>>("deleted_at")?.is_some(),
+        })
+    }
+}
+
+/// A moderator's request to reveal the author of a confession.
+#[derive(Debug, Clone)]
+pub struct RevealRequest {
+    pub guild_id: GuildId,
+    pub confession_number: i64,
+    pub requested_by: UserId,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl RevealRequest {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            guild_id: GuildId::new(row.get::<_, i64>("guild_id")? as u64),
+            confession_number: row.get("confession_number")?,
+            requested_by: UserId::new(row.get::<_, i64>("requested_by")? as u64),
+            expires_at: timestamp(row.get("expires_at")?),
+        })
+    }
+}
+
+/// A ban preventing the author of a confession from submitting new ones.
+#[derive(Debug, Clone)]
+pub struct Ban {
+    pub confession_number: i64,
+    pub reason: Option<String>,
+    pub banned_by: UserId,
+    // `None` for permanent bans
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl Ban {
+    fn from_row(row: &Row<'_>) -> rusqlite::Result<Self> {
+        Ok(Self {
+            confession_number: row.get("confession_number")?,
+            reason: row.get("reason")?,
+            banned_by: UserId::new(row.get::<_, i64>("banned_by")? as u64),
+            expires_at: row.get::<_, Option<i64>>("expires_at")?.map(timestamp),
+        })
+    }
+}
+
+/// Why a reveal request could not be approved.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RevealApprovalError {
+    NotFound,
+    AlreadyHandled,
+    Expired,
+    SameModerator,
+}
+
+/// Brings databases created by older versions up to date with `ADDED_COLUMNS`.
+fn add_missing_columns(conn: &Connection) -> rusqlite::Result<()> {
+    for (table, column, definition) in ADDED_COLUMNS {
+        let exists = conn
+            .prepare(&format!(
+                "SELECT 1 FROM pragma_table_info('{}') WHERE name = ?1",
+                table
+            ))?
+            .exists(params![column])?;
+
+        if !exists {
+            conn.execute(
+                &format!("ALTER TABLE {} ADD COLUMN {} {}", table, column, definition),
+                [],
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+fn timestamp(secs: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(secs, 0).unwrap_or_default()
+}
+
+/// The end of the moderation audit chain: how many entries it has and the hash of the newest.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct AuditHead {
+    count: u64,
+    hash: String,
+}
+
+impl Default for AuditHead {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            hash: AUDIT_GENESIS_HASH.to_string(),
+        }
+    }
+}
+
+/// How the moderation audit trail was tampered with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditTampering {
+    // The entry was edited or inserted, or the entry before it was deleted
+    Entry(i64),
+    // The newest entries were deleted or replaced since the recorded head
+    Head { recorded: u64, found: u64 },
+    // The entries link up, but the head recorded outside the database is gone
+    MissingHead,
+}
+
+impl fmt::Display for AuditTampering {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Entry(id) => write!(f, "starting at entry {}", id),
+            Self::Head { recorded, found } => write!(
+                f,
+                "it should have {} entries up to the recorded head but has {} that don't end there",
+                recorded, found
+            ),
+            Self::MissingHead => write!(f, "its recorded head in {} is missing", AUDIT_HEAD_FILE),
+        }
+    }
+}
+
+/// SQLite-backed storage for everything that must survive a bot restart.
+pub struct Storage {
+    conn: Mutex<Connection>,
+    // Held while a guild's next confession is numbered and posted, keeping the numbering gap-free
+    numbering_locks: Mutex<HashMap<GuildId, Arc<AsyncMutex<()>>>>,
+    // Keys the hashes of the moderation audit chain; kept in the configuration, not the database
+    audit_secret: String,
+    audit_head_file: PathBuf,
+}
+
+impl Storage {
+    /// Opens (or creates) `confessions.db` and makes sure the schema is up to date. The
+    /// moderation audit trail is chained with `audit_secret`.
+    pub fn open(audit_secret: &str) -> rusqlite::Result<Self> {
+        Self::with_connection(
+            Connection::open(DATABASE_FILE)?,
+            audit_secret,
+            PathBuf::from(AUDIT_HEAD_FILE),
+        )
+    }
+
+    fn with_connection(
+        conn: Connection,
+        audit_secret: &str,
+        audit_head_file: PathBuf,
+    ) -> rusqlite::Result<Self> {
+        // Overwrite deleted rows instead of leaving them in free pages of the file
+        conn.pragma_update(None, "secure_delete", true)?;
+        conn.execute_batch(SCHEMA)?;
+        add_missing_columns(&conn)?;
+
+        // Older versions kept the full text of posted confessions and replies
+        conn.execute(
+            "UPDATE confessions SET content = substr(content, 1, ?1) || '…' WHERE length(content) > ?2",
+            params![
+                CONFESSION_PREVIEW_CHARS as i64 - 1,
+                CONFESSION_PREVIEW_CHARS as i64
+            ],
+        )?;
+        conn.execute("UPDATE replies SET content = '' WHERE content != ''", [])?;
+
+        // A moderator may have been halfway through approving a confession when the bot stopped
+        conn.execute("UPDATE pending_confessions SET claimed = 0", [])?;
+
+        let storage = Self {
+            conn: Mutex::new(conn),
+            numbering_locks: Mutex::new(HashMap::new()),
+            audit_secret: audit_secret.to_string(),
+            audit_head_file,
+        };
+
+        storage.key_legacy_moderation_audit()?;
+        if let Some(tampering) = storage.verify_moderation_audit()? {
+            log::error!(
+                "The moderation audit trail has been tampered with: {}.",
+                tampering
+            );
+        }
+
+        Ok(storage)
+    }
+
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().expect("Database mutex poisoned")
+    }
+
+    /// Queues a confession for review and returns its pending id.
+    pub fn insert_pending(
+        &self,
+        guild_id: GuildId,
+        author_hash: &str,
+        draft: &Draft,
+        interaction_token: &str,
+    ) -> rusqlite::Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO pending_confessions (guild_id, author_hash, content, interaction_token, forum_tag, board, content_warning, title, signature, image, image_name, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                guild_id.get() as i64,
+                author_hash,
+                draft.content,
+                interaction_token,
+                draft.forum_tag.map(|tag| tag.get() as i64),
+                draft.board,
+                draft.content_warning,
+                draft.title,
+                draft.signature,
+                draft.image.as_ref().map(|image| &image.data),
+                draft.image.as_ref().map(|image| &image.filename),
+                Utc::now().timestamp()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Returns a pending confession that no moderator is currently acting on.
+    pub fn get_pending(&self, id: i64) -> rusqlite::Result<Option<PendingConfession>> {
+        self.conn()
+            .query_row(
+                "SELECT * FROM pending_confessions WHERE id = ?1 AND claimed = 0",
+                params![id],
+                PendingConfession::from_row,
+            )
+            .optional()
+    }
+
+    /// Marks a pending confession as being handled so that two moderators can't act on it at once.
+    /// Returns `None` if it doesn't exist or has already been claimed.
+    pub fn claim_pending(&self, id: i64) -> rusqlite::Result<Option<PendingConfession>> {
+        let conn = self.conn();
+        let claimed = conn.execute(
+            "UPDATE pending_confessions SET claimed = 1 WHERE id = ?1 AND claimed = 0",
+            params![id],
+        )?;
+
+        if claimed == 0 {
+            return Ok(None);
+        }
+
+        conn.query_row(
+            "SELECT * FROM pending_confessions WHERE id = ?1",
+            params![id],
+            PendingConfession::from_row,
+        )
+        .optional()
+    }
+
+    /// Puts a claimed confession back up for review, e.g. after posting it failed.
+    pub fn release_pending(&self, id: i64) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "UPDATE pending_confessions SET claimed = 0 WHERE id = ?1",
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    /// Replaces the text fields (content, content warning, title and signature) of a confession
+    /// that is still awaiting review. Returns `false` if it has been handled in the meantime.
+    pub fn update_pending_content(&self, id: i64, draft: &Draft) -> rusqlite::Result<bool> {
+        let updated = self.conn().execute(
+            "UPDATE pending_confessions SET content = ?2, content_warning = ?3, title = ?4, signature = ?5 WHERE id = ?1 AND claimed = 0",
+            params![
+                id,
+                draft.content,
+                draft.content_warning,
+                draft.title,
+                draft.signature
+            ],
+        )?;
+        Ok(updated > 0)
+    }
+
+    /// Removes a confession from the review queue.
+    pub fn delete_pending(&self, id: i64) -> rusqlite::Result<()> {
+        self.conn()
+            .execute("DELETE FROM pending_confessions WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Queues a confession to be posted at `post_at`.
+    pub fn insert_scheduled(
+        &self,
+        guild_id: GuildId,
+        author_hash: &str,
+        draft: &Draft,
+        post_at: DateTime<Utc>,
+        created_at: DateTime<Utc>,
+    ) -> rusqlite::Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO scheduled_confessions (guild_id, author_hash, content, forum_tag, board, content_warning, title, signature, image, image_name, post_at, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            params![
+                guild_id.get() as i64,
+                author_hash,
+                draft.content,
+                draft.forum_tag.map(|tag| tag.get() as i64),
+                draft.board,
+                draft.content_warning,
+                draft.title,
+                draft.signature,
+                draft.image.as_ref().map(|image| &image.data),
+                draft.image.as_ref().map(|image| &image.filename),
+                post_at.timestamp(),
+                created_at.timestamp()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Returns the queued confessions whose posting time has come, oldest first.
+    pub fn due_scheduled(&self, now: DateTime<Utc>) -> rusqlite::Result<Vec<ScheduledConfession>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT * FROM scheduled_confessions WHERE post_at <= ?1 ORDER BY post_at, id",
+        )?;
+        statement
+            .query_map(params![now.timestamp()], ScheduledConfession::from_row)?
+            .collect()
+    }
+
+    /// Moves a queued confession that could not be posted to a later time.
+    pub fn retry_scheduled(&self, id: i64, post_at: DateTime<Utc>) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "UPDATE scheduled_confessions SET attempts = attempts + 1, post_at = ?2 WHERE id = ?1",
+            params![id, post_at.timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// Removes a confession from the queue.
+    pub fn delete_scheduled(&self, id: i64) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "DELETE FROM scheduled_confessions WHERE id = ?1",
+            params![id],
+        )?;
+        Ok(())
+    }
+
+    /// Waits until no other confession of this guild is being numbered and posted.
+    /// Keep the returned guard alive until the confession is either posted or discarded.
+    pub async fn lock_numbering(&self, guild_id: GuildId) -> OwnedMutexGuard<()> {
+        let lock = self
+            .numbering_locks
+            .lock()
+            .expect("Numbering lock mutex poisoned")
+            .entry(guild_id)
+            .or_default()
+            .clone();
+
+        lock.lock_owned().await
+    }
+
+    /// Returns the number the guild's next confession would get if it was posted now.
+    pub fn next_confession_number(&self, guild_id: GuildId) -> rusqlite::Result<i64> {
+        self.conn().query_row(
+            "SELECT COALESCE(MAX(number), 0) + 1 FROM confessions WHERE guild_id = ?1",
+            params![guild_id.get() as i64],
+            |row| row.get(0),
+        )
+    }
+
+    /// Assigns the guild's next confession number and records the confession before it is posted.
+    /// Only a preview of the content is stored. Must be called while holding the guild's
+    /// numbering lock.
+    pub fn reserve_confession(
+        &self,
+        guild_id: GuildId,
+        author_hash: &str,
+        content: &str,
+        channel_id: ChannelId,
+        board: Option<&str>,
+        created_at: DateTime<Utc>,
+    ) -> rusqlite::Result<ReservedConfession> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let number: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(number), 0) + 1 FROM confessions WHERE guild_id = ?1",
+            params![guild_id.get() as i64],
+            |row| row.get(0),
+        )?;
+
+        tx.execute(
+            "INSERT INTO confessions (guild_id, number, author_hash, content, channel_id, board, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                guild_id.get() as i64,
+                number,
+                author_hash,
+                truncate(content, CONFESSION_PREVIEW_CHARS),
+                channel_id.get() as i64,
+                board,
+                created_at.timestamp()
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+
+        Ok(ReservedConfession { id, number })
+    }
+
+    /// Records where a reserved confession was posted.
+    pub fn mark_confession_posted(
+        &self,
+        id: i64,
+        thread_id: Option<ChannelId>,
+        message_id: MessageId,
+    ) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "UPDATE confessions SET thread_id = ?2, message_id = ?3, posted_at = ?4 WHERE id = ?1",
+            params![
+                id,
+                thread_id.map(|thread_id| thread_id.get() as i64),
+                message_id.get() as i64,
+                Utc::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Records the messages holding the second and following parts of a long confession.
+    pub fn mark_confession_parts(
+        &self,
+        id: i64,
+        part_message_ids: &[MessageId],
+    ) -> rusqlite::Result<()> {
+        let part_message_ids: Vec<String> = part_message_ids
+            .iter()
+            .map(|message_id| message_id.to_string())
+            .collect();
+
+        self.conn().execute(
+            "UPDATE confessions SET part_message_ids = ?2 WHERE id = ?1",
+            params![id, part_message_ids.join(" ")],
+        )?;
+        Ok(())
+    }
+
+    /// Drops a reserved confession that could not be posted, freeing its number again.
+    pub fn discard_confession(&self, id: i64) -> rusqlite::Result<()> {
+        self.conn()
+            .execute("DELETE FROM confessions WHERE id = ?1", params![id])?;
+        Ok(())
+    }
+
+    /// Returns the most recent posted, undeleted confessions written under any of the hashes.
+    pub fn recent_confessions_by(
+        &self,
+        guild_id: GuildId,
+        author_hashes: &[String],
+        limit: u32,
+    ) -> rusqlite::Result<Vec<Confession>> {
+        if author_hashes.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let sql = format!(
+            "SELECT * FROM confessions WHERE guild_id = ? AND posted_at IS NOT NULL AND deleted_at IS NULL
+             AND author_hash IN ({})
+             ORDER BY number DESC LIMIT {}",
+            vec!["?"; author_hashes.len()].join(", "),
+            limit
+        );
+        let values = std::iter::once(Value::Integer(guild_id.get() as i64))
+            .chain(author_hashes.iter().cloned().map(Value::Text));
+
+        let conn = self.conn();
+        let mut statement = conn.prepare(&sql)?;
+        statement
+            .query_map(params_from_iter(values), Confession::from_row)?
+            .collect()
+    }
+
+    /// Marks a confession as deleted by its author. Returns `false` if it already was.
+    pub fn mark_confession_deleted(
+        &self,
+        guild_id: GuildId,
+        number: i64,
+    ) -> rusqlite::Result<bool> {
+        let changed = self.conn().execute(
+            "UPDATE confessions SET deleted_at = ?3 WHERE guild_id = ?1 AND number = ?2 AND deleted_at IS NULL",
+            params![guild_id.get() as i64, number, Utc::now().timestamp()],
+        )?;
+        Ok(changed > 0)
+    }
+
+    /// Records an accepted submission for rate limiting.
+    pub fn record_submission(&self, guild_id: GuildId, author_hash: &str) -> rusqlite::Result<()> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO submissions (guild_id, author_hash, created_at) VALUES (?1, ?2, ?3)",
+            params![guild_id.get() as i64, author_hash, Utc::now().timestamp()],
+        )?;
+
+        // Nothing looks further back than a day, so older entries can go
+        conn.execute(
+            "DELETE FROM submissions WHERE created_at < ?1",
+            params![(Utc::now() - chrono::Duration::days(2)).timestamp()],
+        )?;
+        Ok(())
+    }
+
+    /// Returns the times of a guild's submissions since `since`, optionally only those made
+    /// under one of the given author hashes.
+    pub fn submission_times(
+        &self,
+        guild_id: GuildId,
+        author_hashes: Option<&[String]>,
+        since: DateTime<Utc>,
+    ) -> rusqlite::Result<Vec<DateTime<Utc>>> {
+        let mut sql =
+            "SELECT created_at FROM submissions WHERE guild_id = ? AND created_at > ?".to_string();
+        let mut values = vec![
+            Value::Integer(guild_id.get() as i64),
+            Value::Integer(since.timestamp()),
+        ];
+
+        if let Some(author_hashes) = author_hashes {
+            if author_hashes.is_empty() {
+                return Ok(Vec::new());
+            }
+
+            sql.push_str(&format!(
+                " AND author_hash IN ({})",
+                vec!["?"; author_hashes.len()].join(", ")
+            ));
+            values.extend(author_hashes.iter().cloned().map(Value::Text));
+        }
+
+        let conn = self.conn();
+        let mut statement = conn.prepare(&sql)?;
+        statement
+            .query_map(params_from_iter(values), |row| Ok(timestamp(row.get(0)?)))?
+            .collect()
+    }
+
+    /// Returns a guild's confession by its number.
+    pub fn get_confession(
+        &self,
+        guild_id: GuildId,
+        number: i64,
+    ) -> rusqlite::Result<Option<Confession>> {
+        self.conn()
+            .query_row(
+                "SELECT * FROM confessions WHERE guild_id = ?1 AND number = ?2",
+                params![guild_id.get() as i64, number],
+                Confession::from_row,
+            )
+            .optional()
+    }
+
+    /// Opens a request to reveal the author of a confession, valid until `expires_at`.
+    pub fn create_reveal_request(
+        &self,
+        guild_id: GuildId,
+        confession_number: i64,
+        requested_by: UserId,
+        expires_at: DateTime<Utc>,
+    ) -> rusqlite::Result<i64> {
+        let conn = self.conn();
+        conn.execute(
+            "INSERT INTO reveal_requests (guild_id, confession_number, requested_by, created_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                guild_id.get() as i64,
+                confession_number,
+                requested_by.get() as i64,
+                Utc::now().timestamp(),
+                expires_at.timestamp()
+            ],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Records a second moderator's approval of a reveal request.
+    /// The approver must differ from the requester and the request must not have expired.
+    pub fn approve_reveal_request(
+        &self,
+        id: i64,
+        approved_by: UserId,
+    ) -> rusqlite::Result<Result<RevealRequest, RevealApprovalError>> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let Some((request, status)) = tx
+            .query_row(
+                "SELECT * FROM reveal_requests WHERE id = ?1",
+                params![id],
+                |row| {
+                    Ok((
+                        RevealRequest::from_row(row)?,
+                        row.get::<_, String>("status")?,
+                    ))
+                },
+            )
+            .optional()?
+        else {
+            return Ok(Err(RevealApprovalError::NotFound));
+        };
+
+        if status != "pending" {
+            return Ok(Err(RevealApprovalError::AlreadyHandled));
+        }
+
+        if request.requested_by == approved_by {
+            return Ok(Err(RevealApprovalError::SameModerator));
+        }
+
+        if request.expires_at < Utc::now() {
+            tx.execute(
+                "UPDATE reveal_requests SET status = 'expired' WHERE id = ?1",
+                params![id],
+            )?;
+            tx.commit()?;
+            return Ok(Err(RevealApprovalError::Expired));
+        }
+
+        tx.execute(
+            "UPDATE reveal_requests SET status = 'approved', approved_by = ?2 WHERE id = ?1",
+            params![id, approved_by.get() as i64],
+        )?;
+        tx.commit()?;
+
+        Ok(Ok(request))
+    }
+
+    /// Bans an author hash from submitting confessions in a guild.
+    pub fn insert_ban(
+        &self,
+        guild_id: GuildId,
+        author_hash: &str,
+        ban: &Ban,
+    ) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "INSERT INTO bans (guild_id, author_hash, confession_number, reason, banned_by, created_at, expires_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                guild_id.get() as i64,
+                author_hash,
+                ban.confession_number,
+                ban.reason,
+                ban.banned_by.get() as i64,
+                Utc::now().timestamp(),
+                ban.expires_at.map(|expires_at| expires_at.timestamp())
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Lifts every ban on an author hash in a guild and returns how many were removed.
+    pub fn delete_bans(&self, guild_id: GuildId, author_hash: &str) -> rusqlite::Result<usize> {
+        self.conn().execute(
+            "DELETE FROM bans WHERE guild_id = ?1 AND author_hash = ?2",
+            params![guild_id.get() as i64, author_hash],
+        )
+    }
+
+    /// Returns the bans of a guild that haven't expired yet, newest first.
+    pub fn active_bans(&self, guild_id: GuildId) -> rusqlite::Result<Vec<Ban>> {
+        let conn = self.conn();
+        let mut statement = conn.prepare(
+            "SELECT * FROM bans WHERE guild_id = ?1 AND (expires_at IS NULL OR expires_at > ?2)
+             ORDER BY created_at DESC",
+        )?;
+        statement
+            .query_map(
+                params![guild_id.get() as i64, Utc::now().timestamp()],
+                Ban::from_row,
+            )?
+            .collect()
+    }
+
+    /// Returns the longest-running active ban matching any of the given author hashes.
+    pub fn find_active_ban(
+        &self,
+        guild_id: GuildId,
+        author_hashes: &[String],
+    ) -> rusqlite::Result<Option<Ban>> {
+        if author_hashes.is_empty() {
+            return Ok(None);
+        }
+
+        let sql = format!(
+            "SELECT * FROM bans WHERE guild_id = ? AND (expires_at IS NULL OR expires_at > ?)
+             AND author_hash IN ({})
+             ORDER BY expires_at IS NULL DESC, expires_at DESC LIMIT 1",
+            vec!["?"; author_hashes.len()].join(", ")
+        );
+        let values = [
+            Value::Integer(guild_id.get() as i64),
+            Value::Integer(Utc::now().timestamp()),
+        ]
+        .into_iter()
+        .chain(author_hashes.iter().cloned().map(Value::Text));
+
+        self.conn()
+            .query_row(&sql, params_from_iter(values), Ban::from_row)
+            .optional()
+    }
+
+    /// Returns the number under which an author replies to a confession, handing out the next
+    /// free one on their first reply. Any of the author's current or recent hashes is recognised.
+    pub fn reply_alias(
+        &self,
+        guild_id: GuildId,
+        confession_number: i64,
+        author_hashes: &[String],
+    ) -> rusqlite::Result<i64> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let sql = format!(
+            "SELECT alias FROM reply_aliases WHERE guild_id = ? AND confession_number = ?
+             AND author_hash IN ({}) LIMIT 1",
+            vec!["?"; author_hashes.len()].join(", ")
+        );
+        let values = [
+            Value::Integer(guild_id.get() as i64),
+            Value::Integer(confession_number),
+        ]
+        .into_iter()
+        .chain(author_hashes.iter().cloned().map(Value::Text));
+
+        if let Some(alias) = tx
+            .query_row(&sql, params_from_iter(values), |row| row.get(0))
+            .optional()?
+        {
+            return Ok(alias);
+        }
+
+        let alias: i64 = tx.query_row(
+            "SELECT COALESCE(MAX(alias), 0) + 1 FROM reply_aliases
+             WHERE guild_id = ?1 AND confession_number = ?2",
+            params![guild_id.get() as i64, confession_number],
+            |row| row.get(0),
+        )?;
+
+        tx.execute(
+            "INSERT INTO reply_aliases (guild_id, confession_number, author_hash, alias)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                guild_id.get() as i64,
+                confession_number,
+                author_hashes
+                    .first()
+                    .map(String::as_str)
+                    .unwrap_or_default(),
+                alias
+            ],
+        )?;
+        tx.commit()?;
+
+        Ok(alias)
+    }
+
+    /// Stores an anonymous reply that was posted in a confession's thread. Its content is only
+    /// in the posted message and the audit log.
+    pub fn insert_reply(
+        &self,
+        guild_id: GuildId,
+        confession_number: i64,
+        author_hash: &str,
+        thread_id: ChannelId,
+        message_id: MessageId,
+    ) -> rusqlite::Result<()> {
+        self.conn().execute(
+            "INSERT INTO replies (guild_id, confession_number, author_hash, content, thread_id, message_id, created_at)
+             VALUES (?1, ?2, ?3, '', ?4, ?5, ?6)",
+            params![
+                guild_id.get() as i64,
+                confession_number,
+                author_hash,
+                thread_id.get() as i64,
+                message_id.get() as i64,
+                Utc::now().timestamp()
+            ],
+        )?;
+        Ok(())
+    }
+
+    /// Returns whether the head recorded outside the database is that of the chain as stored.
+    /// A trail without entries needs no recorded head.
+    fn is_recorded_head(&self, head: &AuditHead) -> bool {
+        match self.read_audit_head() {
+            Some(recorded) => recorded == *head,
+            None => head.count == 0,
+        }
+    }
+
+    fn read_audit_head(&self) -> Option<AuditHead> {
+        match std::fs::read_to_string(&self.audit_head_file) {
+            Ok(content) => match serde_json::from_str(&content) {
+                Ok(head) => Some(head),
+                Err(e) => {
+                    log::error!(
+                        "Failed to read the moderation audit head from {}: {}",
+                        self.audit_head_file.display(),
+                        e
+                    );
+                    None
+                }
+            },
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => {
+                log::error!(
+                    "Failed to read the moderation audit head from {}: {}",
+                    self.audit_head_file.display(),
+                    e
+                );
+                None
+            }
+        }
+    }
+
+    /// Records the head of the chain, replacing the file at once so a crash can't leave half of it.
+    fn write_audit_head(&self, head: &AuditHead) {
+        let temporary = self.audit_head_file.with_extension("tmp");
+        let result = serde_json::to_string(head)
+            .map_err(io::Error::other)
+            .and_then(|json| std::fs::write(&temporary, json))
+            .and_then(|()| std::fs::rename(&temporary, &self.audit_head_file));
+
+        if let Err(e) = result {
+            log::error!(
+                "Failed to record the moderation audit head in {}: {:?}",
+                self.audit_head_file.display(),
+                e
+            );
+        }
+    }
+
+    /// Chains a trail written before the chain was keyed anew with the secret. Such trails are
+    /// hashed with plain SHA-256 and have no recorded head; they are only keyed if intact.
+    fn key_legacy_moderation_audit(&self) -> rusqlite::Result<()> {
+        if self.read_audit_head().is_some() {
+            return Ok(());
+        }
+
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let Ok(legacy) = walk_moderation_audit(&tx, None)? else {
+            return Ok(());
+        };
+        if legacy.count == 0 {
+            return Ok(());
+        }
+
+        let head = rechain_moderation_audit(&tx, &self.audit_secret)?;
+        tx.commit()?;
+        self.write_audit_head(&head);
+
+        log::warn!(
+            "Keyed the {} entries of the moderation audit trail with moderation_audit_secret.",
+            head.count
+        );
+        Ok(())
+    }
+
+    /// Appends an entry to the moderation audit trail.
+    /// Every entry includes the keyed hash of the previous one, so editing or deleting rows
+    /// breaks the chain, and the newest entry is recorded outside the database so that deleting
+    /// the newest rows is noticed too.
+    pub fn append_moderation_audit(
+        &self,
+        guild_id: GuildId,
+        action: &str,
+        actor_id: UserId,
+        confession_number: Option<i64>,
+        details: &str,
+    ) -> rusqlite::Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+
+        let previous = stored_audit_head(&tx)?;
+        let recorded = self.is_recorded_head(&previous);
+
+        let created_at = Utc::now().timestamp();
+        let hash = audit_entry_hash(
+            Some(&self.audit_secret),
+            &previous.hash,
+            guild_id.get() as i64,
+            action,
+            actor_id.get() as i64,
+            confession_number,
+            details,
+            created_at,
+        );
+
+        tx.execute(
+            "INSERT INTO moderation_audit (guild_id, action, actor_id, confession_number, details, created_at, prev_hash, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                guild_id.get() as i64,
+                action,
+                actor_id.get() as i64,
+                confession_number,
+                details,
+                created_at,
+                previous.hash,
+                hash
+            ],
+        )?;
+        tx.commit()?;
+
+        // A trail that no longer ends where it was recorded to keeps that evidence
+        if recorded {
+            self.write_audit_head(&AuditHead {
+                count: previous.count + 1,
+                hash,
+            });
+        } else {
+            log::error!(
+                "The moderation audit trail doesn't end at its recorded head, so the new entry is not recorded as the head."
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Walks the moderation audit chain and compares its end with the head recorded outside the
+    /// database. Returns what was tampered with, or `None` if the trail is intact.
+    pub fn verify_moderation_audit(&self) -> rusqlite::Result<Option<AuditTampering>> {
+        let head = match walk_moderation_audit(&self.conn(), Some(&self.audit_secret))? {
+            Ok(head) => head,
+            Err(id) => return Ok(Some(AuditTampering::Entry(id))),
+        };
+
+        Ok(match self.read_audit_head() {
+            Some(recorded) if recorded == head => None,
+            Some(recorded) => Some(AuditTampering::Head {
+                recorded: recorded.count,
+                found: head.count,
+            }),
+            None if head.count == 0 => None,
+            None => Some(AuditTampering::MissingHead),
+        })
+    }
+
+    /// Deletes or redacts the moderation audit entries of a guild (or of every guild) written
+    /// before `before` (or all of them), and returns how many were purged. The remaining entries
+    /// are then chained anew, but only if the trail was intact before, so that earlier tampering
+    /// is still reported.
+    pub fn purge_moderation_audit(
+        &self,
+        guild_id: Option<GuildId>,
+        before: Option<DateTime<Utc>>,
+        action: RetentionAction,
+    ) -> rusqlite::Result<usize> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let intact = walk_moderation_audit(&tx, Some(&self.audit_secret))?
+            .is_ok_and(|head| self.is_recorded_head(&head));
+
+        let guild_id = guild_id.map(|guild_id| guild_id.get() as i64);
+        let before = before.map(|before| before.timestamp());
+        let purged = match action {
+            RetentionAction::Delete => tx.execute(
+                "DELETE FROM moderation_audit
+                 WHERE (?1 IS NULL OR guild_id = ?1) AND (?2 IS NULL OR created_at < ?2)",
+                params![guild_id, before],
+            )?,
+            RetentionAction::Redact => tx.execute(
+                "UPDATE moderation_audit SET details = ?3
+                 WHERE (?1 IS NULL OR guild_id = ?1) AND (?2 IS NULL OR created_at < ?2) AND details != ?3",
+                params![guild_id, before, REDACTED_AUDIT_DETAILS],
+            )?,
+        };
+
+        if purged == 0 {
+            return Ok(0);
+        }
+
+        if intact {
+            let head = rechain_moderation_audit(&tx, &self.audit_secret)?;
+            tx.commit()?;
+            self.write_audit_head(&head);
+        } else {
+            tx.commit()?;
+            log::error!(
+                "The moderation audit trail was tampered with before this purge, so it is not chained anew."
+            );
+        }
+
+        Ok(purged)
+    }
+
+    /// Deletes a guild's confessions, replies, queues and bans, and returns how many rows were
+    /// deleted. Its moderation audit entries are purged with `purge_moderation_audit`.
+    pub fn delete_guild_data(&self, guild_id: GuildId) -> rusqlite::Result<usize> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let mut deleted = 0;
+
+        for table in GUILD_TABLES {
+            deleted += tx.execute(
+                &format!("DELETE FROM {} WHERE guild_id = ?1", table),
+                params![guild_id.get() as i64],
+            )?;
+        }
+
+        tx.commit()?;
+        self.numbering_locks
+            .lock()
+            .expect("Numbering lock mutex poisoned")
+            .remove(&guild_id);
+
+        Ok(deleted)
+    }
+}
+
+/// Returns the head of the moderation audit chain as stored, without checking its entries.
+fn stored_audit_head(conn: &Connection) -> rusqlite::Result<AuditHead> {
+    conn.query_row(
+        "SELECT COUNT(*), (SELECT hash FROM moderation_audit ORDER BY id DESC LIMIT 1)
+         FROM moderation_audit",
+        [],
+        |row| {
+            Ok(AuditHead {
+                count: row.get::<_, i64>(0)? as u64,
+                hash: row
+                    .get::<_, Option<String>>(1)?
+                    .unwrap_or_else(|| AUDIT_GENESIS_HASH.to_string()),
+            })
+        },
+    )
+}
+
+/// Walks the moderation audit chain, hashing with `secret` (or plain SHA-256 for trails from
+/// before the chain was keyed). Returns the head of the chain, or the id of the first entry that
+/// doesn't match its hash or doesn't link to the one before it.
+fn walk_moderation_audit(
+    conn: &Connection,
+    secret: Option<&str>,
+) -> rusqlite::Result<Result<AuditHead, i64>> {
+    let mut statement = conn.prepare(
+        "SELECT id, guild_id, action, actor_id, confession_number, details, created_at, prev_hash, hash
+         FROM moderation_audit ORDER BY id",
+    )?;
+    let mut rows = statement.query([])?;
+    let mut head = AuditHead::default();
+
+    while let Some(row) = rows.next()? {
+        let id: i64 = row.get(0)?;
+        let prev_hash: String = row.get(7)?;
+        let hash: String = row.get(8)?;
+        let computed = audit_entry_hash(
+            secret,
+            &prev_hash,
+            row.get(1)?,
+            &row.get::<_, String>(2)?,
+            row.get(3)?,
+            row.get(4)?,
+            &row.get::<_, String>(5)?,
+            row.get(6)?,
+        );
+
+        if prev_hash != head.hash || hash != computed {
+            return Ok(Err(id));
+        }
+
+        head = AuditHead {
+            count: head.count + 1,
+            hash,
+        };
+    }
+
+    Ok(Ok(head))
+}
+
+/// Links every moderation audit entry to the one before it again with keyed hashes, starting
+/// from the genesis hash, and returns the new head.
+fn rechain_moderation_audit(conn: &Connection, secret: &str) -> rusqlite::Result<AuditHead> {
+    let entries = conn
+        .prepare(
+            "SELECT id, guild_id, action, actor_id, confession_number, details, created_at
+             FROM moderation_audit ORDER BY id",
+        )?
+        .query_map([], |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+                row.get::<_, String>(5)?,
+                row.get::<_, i64>(6)?,
+            ))
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut update =
+        conn.prepare("UPDATE moderation_audit SET prev_hash = ?2, hash = ?3 WHERE id = ?1")?;
+    let mut head = AuditHead::default();
+
+    for (id, guild_id, action, actor_id, confession_number, details, created_at) in entries {
+        let hash = audit_entry_hash(
+            Some(secret),
+            &head.hash,
+            guild_id,
+            &action,
+            actor_id,
+            confession_number,
+            &details,
+            created_at,
+        );
+        update.execute(params![id, head.hash, hash])?;
+        head = AuditHead {
+            count: head.count + 1,
+            hash,
+        };
+    }
+
+    Ok(head)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn audit_entry_hash(
+    secret: Option<&str>,
+    prev_hash: &str,
+    guild_id: i64,
+    action: &str,
+    actor_id: i64,
+    confession_number: Option<i64>,
+    details: &str,
+    created_at: i64,
+) -> String {
+    let confession_number = confession_number.map(|n| n.to_string()).unwrap_or_default();
+    let entry = format!(
+        "{}|{}|{}|{}|{}|{}|{}",
+        prev_hash, guild_id, action, actor_id, confession_number, details, created_at
+    );
+
+    match secret {
+        Some(secret) => {
+            let mut mac = HmacSha256::new_from_slice(secret.as_bytes())
+                .expect("HMAC accepts keys of any length");
+            mac.update(entry.as_bytes());
+            format!("{:x}", mac.finalize().into_bytes())
+        }
+        // Trails written before the chain was keyed
+        None => format!("{:x}", Sha256::digest(entry)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &str = "moderation audit secret";
+
+    /// A head file of its own for every storage, since tests run in parallel.
+    fn head_file() -> PathBuf {
+        static NEXT: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let index = NEXT.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        let path = std::env::temp_dir().join(format!(
+            "moderation_audit_head_{}_{}.json",
+            std::process::id(),
+            index
+        ));
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    fn storage() -> Storage {
+        Storage::with_connection(Connection::open_in_memory().unwrap(), SECRET, head_file())
+            .unwrap()
+    }
+
+    fn append(storage: &Storage, guild_id: u64, details: &str) {
+        storage
+            .append_moderation_audit(
+                GuildId::new(guild_id),
+                "author_banned",
+                UserId::new(7),
+                Some(1),
+                details,
+            )
+            .unwrap();
+    }
+
+    fn age_entries(storage: &Storage, guild_id: u64, days: i64) {
+        storage
+            .conn()
+            .execute(
+                "UPDATE moderation_audit SET created_at = created_at - ?2 WHERE guild_id = ?1",
+                params![guild_id as i64, days * 24 * 60 * 60],
+            )
+            .unwrap();
+        // Aging entries by hand edits them, so the chain has to be rebuilt for the test
+        let head = rechain_moderation_audit(&storage.conn(), SECRET).unwrap();
+        storage.write_audit_head(&head);
+    }
+
+    fn details(storage: &Storage) -> Vec<String> {
+        storage
+            .conn()
+            .prepare("SELECT details FROM moderation_audit ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<rusqlite::Result<_>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn purging_a_guild_keeps_the_chain_intact() {
+        let storage = storage();
+        append(&storage, 1, "first");
+        append(&storage, 2, "second");
+        append(&storage, 1, "third");
+        append(&storage, 2, "fourth");
+
+        let purged = storage
+            .purge_moderation_audit(Some(GuildId::new(1)), None, RetentionAction::Delete)
+            .unwrap();
+
+        assert_eq!(purged, 2);
+        assert_eq!(details(&storage), ["second", "fourth"]);
+        assert_eq!(storage.verify_moderation_audit().unwrap(), None);
+
+        append(&storage, 2, "fifth");
+        assert_eq!(storage.verify_moderation_audit().unwrap(), None);
+    }
+
+    #[test]
+    fn only_entries_past_the_cutoff_are_purged() {
+        let storage = storage();
+        append(&storage, 1, "old");
+        age_entries(&storage, 1, 30);
+        append(&storage, 2, "new");
+
+        let before = Some(Utc::now() - chrono::Duration::days(7));
+        let purged = storage
+            .purge_moderation_audit(None, before, RetentionAction::Redact)
+            .unwrap();
+
+        assert_eq!(purged, 1);
+        assert_eq!(details(&storage), [REDACTED_AUDIT_DETAILS, "new"]);
+        assert_eq!(storage.verify_moderation_audit().unwrap(), None);
+
+        // Redacted entries aren't counted again
+        let purged = storage
+            .purge_moderation_audit(None, before, RetentionAction::Redact)
+            .unwrap();
+        assert_eq!(purged, 0);
+
+        let purged = storage
+            .purge_moderation_audit(None, before, RetentionAction::Delete)
+            .unwrap();
+        assert_eq!(purged, 1);
+        assert_eq!(details(&storage), ["new"]);
+        assert_eq!(storage.verify_moderation_audit().unwrap(), None);
+    }
+
+    #[test]
+    fn purging_doesnt_hide_earlier_tampering() {
+        let storage = storage();
+        append(&storage, 1, "first");
+        append(&storage, 2, "second");
+        append(&storage, 2, "third");
+        storage
+            .conn()
+            .execute(
+                "UPDATE moderation_audit SET details = 'edited' WHERE details = 'second'",
+                [],
+            )
+            .unwrap();
+
+        storage
+            .purge_moderation_audit(Some(GuildId::new(1)), None, RetentionAction::Delete)
+            .unwrap();
+
+        assert!(storage.verify_moderation_audit().unwrap().is_some());
+    }
+
+    #[test]
+    fn rehashing_an_edit_without_the_secret_is_detected() {
+        let storage = storage();
+        append(&storage, 1, "first");
+        append(&storage, 1, "second");
+        append(&storage, 1, "third");
+        assert_eq!(storage.verify_moderation_audit().unwrap(), None);
+
+        // Someone with access to the database edits an entry and recomputes every hash after
+        // it, but without the secret
+        storage
+            .conn()
+            .execute(
+                "UPDATE moderation_audit SET details = 'edited' WHERE details = 'second'",
+                [],
+            )
+            .unwrap();
+        rechain_moderation_audit(&storage.conn(), "guessed secret").unwrap();
+
+        assert_eq!(
+            storage.verify_moderation_audit().unwrap(),
+            Some(AuditTampering::Entry(1))
+        );
+    }
+
+    #[test]
+    fn deleting_the_newest_entries_is_detected() {
+        let storage = storage();
+        append(&storage, 1, "first");
+        append(&storage, 1, "second");
+        append(&storage, 1, "third");
+
+        storage
+            .conn()
+            .execute("DELETE FROM moderation_audit WHERE details = 'third'", [])
+            .unwrap();
+
+        assert_eq!(
+            storage.verify_moderation_audit().unwrap(),
+            Some(AuditTampering::Head {
+                recorded: 3,
+                found: 2
+            })
+        );
+
+        // New entries don't cover it up
+        append(&storage, 1, "fourth");
+        assert!(storage.verify_moderation_audit().unwrap().is_some());
+    }
+
+    #[test]
+    fn a_missing_head_is_detected() {
+        let storage = storage();
+        append(&storage, 1, "first");
+        std::fs::remove_file(&storage.audit_head_file).unwrap();
+
+        assert_eq!(
+            storage.verify_moderation_audit().unwrap(),
+            Some(AuditTampering::MissingHead)
+        );
+    }
+
+    #[test]
+    fn trails_from_before_the_secret_are_keyed() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+
+        let mut prev_hash = AUDIT_GENESIS_HASH.to_string();
+        for details in ["first", "second"] {
+            let hash =
+                audit_entry_hash(None, &prev_hash, 1, "author_banned", 7, Some(1), details, 0);
+            conn.execute(
+                "INSERT INTO moderation_audit (guild_id, action, actor_id, confession_number, details, created_at, prev_hash, hash)
+                 VALUES (1, 'author_banned', 7, 1, ?1, 0, ?2, ?3)",
+                params![details, prev_hash, hash],
+            )
+            .unwrap();
+            prev_hash = hash;
+        }
+
+        let storage = Storage::with_connection(conn, SECRET, head_file()).unwrap();
+        assert_eq!(storage.verify_moderation_audit().unwrap(), None);
+        assert_eq!(details(&storage), ["first", "second"]);
+    }
+
+    #[test]
+    fn only_a_preview_of_posted_confessions_is_stored() {
+        let storage = storage();
+        let content = "a".repeat(500);
+        storage
+            .reserve_confession(
+                GuildId::new(1),
+                "hash",
+                &content,
+                ChannelId::new(2),
+                None,
+                Utc::now(),
+            )
+            .unwrap();
+        storage
+            .insert_reply(
+                GuildId::new(1),
+                1,
+                "hash",
+                ChannelId::new(3),
+                MessageId::new(4),
+            )
+            .unwrap();
+
+        let confession = storage.get_confession(GuildId::new(1), 1).unwrap().unwrap();
+        assert_eq!(confession.preview.chars().count(), CONFESSION_PREVIEW_CHARS);
+        assert!(confession.preview.ends_with('…'));
+
+        let reply: String = storage
+            .conn()
+            .query_row("SELECT content FROM replies", [], |row| row.get(0))
+            .unwrap();
+        assert!(reply.is_empty());
+    }
+
+    #[test]
+    fn full_texts_from_older_versions_are_cut_on_open() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(SCHEMA).unwrap();
+        conn.execute(
+            "INSERT INTO confessions (guild_id, number, author_hash, content, channel_id, created_at)
+             VALUES (1, 1, 'hash', ?1, 2, 0)",
+            params!["é".repeat(500)],
+        )
+        .unwrap();
+        conn.execute(
+            "INSERT INTO replies (guild_id, confession_number, author_hash, content, thread_id, message_id, created_at)
+             VALUES (1, 1, 'hash', 'a reply', 3, 4, 0)",
+            [],
+        )
+        .unwrap();
+
+        let storage = Storage::with_connection(conn, SECRET, head_file()).unwrap();
+        let confession = storage.get_confession(GuildId::new(1), 1).unwrap().unwrap();
+        assert_eq!(
+            confession.preview,
+            format!("{}…", "é".repeat(CONFESSION_PREVIEW_CHARS - 1))
+        );
+
+        let reply: String = storage
+            .conn()
+            .query_row("SELECT content FROM replies", [], |row| row.get(0))
+            .unwrap();
+        assert!(reply.is_empty());
+    }
+
+    #[test]
+    fn deleting_a_guild_leaves_other_guilds_alone() {
+        let storage = storage();
+        let ban = Ban {
+            confession_number: 1,
+            reason: None,
+            banned_by: UserId::new(7),
+            expires_at: None,
+        };
+        for guild_id in [1, 2] {
+            storage
+                .insert_ban(GuildId::new(guild_id), "hash", &ban)
+                .unwrap();
+            storage
+                .record_submission(GuildId::new(guild_id), "hash")
+                .unwrap();
+        }
+
+        assert_eq!(storage.delete_guild_data(GuildId::new(1)).unwrap(), 2);
+        assert!(storage.active_bans(GuildId::new(1)).unwrap().is_empty());
+        assert_eq!(storage.active_bans(GuildId::new(2)).unwrap().len(), 1);
+    }
+}
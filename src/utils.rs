@@ -1,13 +1,475 @@
+use poise::serenity_prelude::{
+    ActionRowComponent, CreateActionRow, CreateInputText, CreateInteractionResponse, CreateModal,
+    InputTextStyle, ModalInteractionData,
+};
+
+use crate::{
+    config::{FieldVisibility, FormField, FormSettings},
+    storage::Draft,
+};
+
 // Custom IDs for the button
 pub const CONFESS_BUTTON_ID: &str = "confess_button";
 
-#[derive(Debug, poise::Modal)]
-#[allow(dead_code)]
-#[allow(non_snake_case)]
+// Longest text a modal input accepts. Longer confessions get a second input to continue in.
+const INPUT_MAX_CHARS: u16 = 4000;
+
+// Custom IDs of the inputs of the confession modal
+const CONTENT_INPUT_ID: &str = "content";
+const CONTENT_CONTINUED_INPUT_ID: &str = "content_continued";
+const TITLE_INPUT_ID: &str = "title";
+const CONTENT_WARNING_INPUT_ID: &str = "content_warning";
+const SIGNATURE_INPUT_ID: &str = "signature";
+
+/// What the author wrote in the confession modal. The modal is built from the guild's form
+/// settings, so the optional fields are `None` when the modal didn't ask for them.
+#[derive(Debug, Default)]
 pub struct ConfessionModal {
-    #[name = "Confession Content"]
-    #[placeholder = "Remember: All confessions are anonymous."]
+    pub content: String,
+    // Rest of a confession longer than one input allows
+    pub content_continued: Option<String>,
+    pub title: Option<String>,
+    pub content_warning: Option<String>,
+    pub signature: Option<String>,
+}
+
+/// Adds an optional field to the modal unless the guild hides it.
+fn optional_input(
+    inputs: &mut Vec<CreateActionRow>,
+    field: FormField,
+    custom_id: &str,
+    label: &str,
+    placeholder: &str,
+    value: Option<&str>,
+) {
+    if field.visibility == FieldVisibility::Hidden {
+        return;
+    }
+
+    let mut input = CreateInputText::new(InputTextStyle::Short, label, custom_id)
+        .placeholder(placeholder)
+        .max_length(field.max_length)
+        .required(field.visibility == FieldVisibility::Required);
+
+    if let Some(value) = value {
+        input = input.value(capped(value, field.max_length));
+    }
+
+    inputs.push(CreateActionRow::InputText(input));
+}
+
+/// Keeps the first `max_chars` characters of a prefilled value, since Discord refuses a modal
+/// whose values are longer than their inputs allow.
+fn capped(value: &str, max_chars: u16) -> String {
+    value.chars().take(max_chars.into()).collect()
+}
+
+/// Splits a prefilled confession over the content input and its continuation, cutting exactly
+/// where the first input is full so that `into_draft` puts it back together unchanged.
+fn split_content(form: &FormSettings, content: &str) -> (String, String) {
+    let first_max_length = form.content_max_length.min(INPUT_MAX_CHARS);
+    let continued_max_length = form.content_max_length.saturating_sub(INPUT_MAX_CHARS);
+
+    let continued = content
+        .chars()
+        .skip(first_max_length.into())
+        .take(continued_max_length.into())
+        .collect();
+
+    (capped(content, first_max_length), continued)
+}
+
+/// Trims a field of the modal. An empty field counts as not filled in.
+fn non_empty(value: String) -> Option<String> {
+    Some(value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+impl ConfessionModal {
+    /// Builds the confession modal with the fields the guild asks for, prefilled with `draft`
+    /// when a moderator edits a pending confession. When the guild accepts confessions longer
+    /// than one input holds, a second input continues the first.
+    pub fn create(
+        form: &FormSettings,
+        draft: Option<&Draft>,
+        custom_id: String,
+    ) -> CreateInteractionResponse {
+        let continued_max_length = form.content_max_length.saturating_sub(INPUT_MAX_CHARS);
+
+        let (prefill, prefill_continued) = match draft {
+            Some(draft) => {
+                let (first, continued) = split_content(form, &draft.content);
+                (Some(first), continued)
+            }
+            None => (None, String::new()),
+        };
+
+        let mut content = CreateInputText::new(
+            InputTextStyle::Paragraph,
+            "Confession Content",
+            CONTENT_INPUT_ID,
+        )
+        .placeholder("Remember: All confessions are anonymous.")
+        .max_length(form.content_max_length.min(INPUT_MAX_CHARS));
+
+        if let Some(prefill) = prefill {
+            content = content.value(prefill);
+        }
+
+        let mut inputs = vec![CreateActionRow::InputText(content)];
+
+        if continued_max_length > 0 {
+            let mut continued = CreateInputText::new(
+                InputTextStyle::Paragraph,
+                "Continued (optional)",
+                CONTENT_CONTINUED_INPUT_ID,
+            )
+            .placeholder("Continue here if your confession doesn't fit above.")
+            .max_length(continued_max_length)
+            .required(false);
+
+            if !prefill_continued.is_empty() {
+                continued = continued.value(prefill_continued);
+            }
+
+            inputs.push(CreateActionRow::InputText(continued));
+        }
+
+        optional_input(
+            &mut inputs,
+            form.title,
+            TITLE_INPUT_ID,
+            "Title",
+            "Shown as the name of the confession's thread.",
+            draft.and_then(|draft| draft.title.as_deref()),
+        );
+        optional_input(
+            &mut inputs,
+            form.content_warning,
+            CONTENT_WARNING_INPUT_ID,
+            "Content warning",
+            "e.g. self-harm. The confession will be hidden behind a spoiler.",
+            draft.and_then(|draft| draft.content_warning.as_deref()),
+        );
+        optional_input(
+            &mut inputs,
+            form.signature,
+            SIGNATURE_INPUT_ID,
+            "Signature",
+            "A pseudonym to sign with. It is not linked to your account.",
+            draft.and_then(|draft| draft.signature.as_deref()),
+        );
+
+        CreateInteractionResponse::Modal(
+            CreateModal::new(custom_id, "Anonymous Confession").components(inputs),
+        )
+    }
+
+    /// Reads the submitted modal. Fails if the confession itself is missing.
+    pub fn parse(data: ModalInteractionData) -> Result<Self, &'static str> {
+        let mut modal = Self::default();
+        let mut has_content = false;
+
+        let inputs = data
+            .components
+            .into_iter()
+            .flat_map(|row| row.components)
+            .filter_map(|component| match component {
+                ActionRowComponent::InputText(input) => Some(input),
+                _ => None,
+            });
+
+        for input in inputs {
+            let value = input.value.unwrap_or_default();
+            match input.custom_id.as_str() {
+                CONTENT_INPUT_ID => {
+                    modal.content = value;
+                    has_content = true;
+                }
+                CONTENT_CONTINUED_INPUT_ID => modal.content_continued = Some(value),
+                TITLE_INPUT_ID => modal.title = Some(value),
+                CONTENT_WARNING_INPUT_ID => modal.content_warning = Some(value),
+                SIGNATURE_INPUT_ID => modal.signature = Some(value),
+                _ => {}
+            }
+        }
+
+        if !has_content {
+            return Err("the confession modal was submitted without its content");
+        }
+
+        Ok(modal)
+    }
+
+    /// Fills the draft with what the author wrote. Empty optional fields count as none, and
+    /// fields the modal didn't ask for keep the value they had in `draft`. The continuation is
+    /// appended to the confession as it is, so a confession prefilled over both inputs comes
+    /// back unchanged.
+    pub fn into_draft(self, draft: Draft) -> Draft {
+        let mut content = self.content;
+        content.push_str(&self.content_continued.unwrap_or_default());
+
+        Draft {
+            content: content.trim().to_string(),
+            title: self.title.map_or(draft.title, non_empty),
+            content_warning: self
+                .content_warning
+                .map_or(draft.content_warning, non_empty),
+            signature: self.signature.map_or(draft.signature, non_empty),
+            ..draft
+        }
+    }
+}
+
+#[derive(Debug, poise::Modal)]
+#[name = "Anonymous Reply"]
+pub struct ReplyModal {
+    #[name = "Reply"]
+    #[placeholder = "Your reply is anonymous, but the confession's author will be shown as OP."]
     #[paragraph]
     #[max_length = 2000]
     pub content: String,
 }
+
+// Prefix of the custom IDs of the "Reply anonymously" buttons, followed by `:<confession number>`
+pub const REPLY_BUTTON_PREFIX: &str = "confession_reply";
+
+// Prefix of the custom IDs of the confession preview buttons, followed by
+// `:<action>:<interaction id>`
+pub const PREVIEW_BUTTON_PREFIX: &str = "confession_preview";
+
+// Prefix of the custom IDs of the category select menus, followed by `:<interaction id>`
+pub const CATEGORY_SELECT_PREFIX: &str = "confession_category";
+
+// Custom ID of the board select menu on the `/confessembed` message
+pub const BOARD_SELECT_ID: &str = "confession_board";
+
+// Prefix of the custom IDs of the review queue buttons, followed by `:<action>:<pending id>`
+pub const REVIEW_BUTTON_PREFIX: &str = "confession_review";
+
+// Prefix of the custom IDs of the audit log page buttons, followed by
+// `:<direction>:<interaction id>`
+pub const AUDIT_BUTTON_PREFIX: &str = "confession_audit";
+
+// Prefix of the custom IDs of the reveal approval buttons, followed by `:<reveal request id>`
+pub const REVEAL_BUTTON_PREFIX: &str = "confession_reveal";
+
+/// Shortens text to at most `max_chars` characters, adding an ellipsis when it was cut.
+pub fn truncate(text: &str, max_chars: usize) -> String {
+    if text.chars().count() <= max_chars {
+        return text.to_string();
+    }
+
+    let mut truncated: String = text.chars().take(max_chars.saturating_sub(1)).collect();
+    truncated.push('…');
+    truncated
+}
+
+/// Splits text into parts of at most `max_chars` characters. Parts end at the last paragraph
+/// break that fits, otherwise at the last line break or space; only a word longer than
+/// `max_chars` is cut in the middle. The breaks themselves are dropped, and the same text
+/// always splits the same way.
+pub fn split_text(text: &str, max_chars: usize) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut rest = text.trim();
+
+    while rest.chars().count() > max_chars {
+        let mut boundaries = rest.char_indices().map(|(index, _)| index).skip(max_chars);
+        // Byte offset after `max_chars` characters, and after one more so that a break right
+        // after a full part is found as well
+        let cut = boundaries.next().unwrap_or(rest.len());
+        let window = &rest[..boundaries.next().unwrap_or(rest.len())];
+
+        let (end, next) = ["\n\n", "\n", " "]
+            .iter()
+            .find_map(|separator| {
+                window
+                    .rfind(separator)
+                    .filter(|&index| index > 0)
+                    .map(|index| (index, index + separator.len()))
+            })
+            .unwrap_or((cut, cut));
+
+        parts.push(rest[..end].trim_end().to_string());
+        rest = rest[next..].trim_start();
+    }
+
+    if !rest.is_empty() {
+        parts.push(rest.to_string());
+    }
+
+    parts
+}
+
+/// Parses a duration such as `30m`, `12h`, `7d`, `2w` or a combination like `1d12h`.
+pub fn parse_duration(input: &str) -> Option<chrono::Duration> {
+    let mut total = chrono::Duration::zero();
+    let mut digits = String::new();
+
+    for c in input.trim().chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+
+        let amount: i64 = digits.parse().ok()?;
+        digits.clear();
+
+        let duration = match c.to_ascii_lowercase() {
+            's' => chrono::Duration::try_seconds(amount)?,
+            'm' => chrono::Duration::try_minutes(amount)?,
+            'h' => chrono::Duration::try_hours(amount)?,
+            'd' => chrono::Duration::try_days(amount)?,
+            'w' => chrono::Duration::try_weeks(amount)?,
+            _ => return None,
+        };
+        total = total.checked_add(&duration)?;
+    }
+
+    // Every number needs a unit, and the total must be positive
+    if !digits.is_empty() || total <= chrono::Duration::zero() {
+        return None;
+    }
+
+    Some(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(form: &FormSettings, content: &str) -> String {
+        let (content, continued) = split_content(form, content);
+        let modal = ConfessionModal {
+            content,
+            content_continued: Some(continued),
+            ..ConfessionModal::default()
+        };
+
+        modal.into_draft(Draft::default()).content
+    }
+
+    #[test]
+    fn prefilled_confessions_survive_an_edit() {
+        let form = FormSettings {
+            content_max_length: 8000,
+            ..FormSettings::default()
+        };
+
+        for content in [
+            "short confession".to_string(),
+            format!("{}\n\n{}", "a".repeat(3999), "b".repeat(2000)),
+            format!("{} {}", "a".repeat(4000), "b".repeat(100)),
+            format!("{}\n{}", "é".repeat(3000), "日本語 ".repeat(700).trim()),
+            "word ".repeat(1500).trim().to_string(),
+        ] {
+            assert_eq!(round_trip(&form, &content), content);
+            // Editing it again changes nothing either
+            assert_eq!(round_trip(&form, &round_trip(&form, &content)), content);
+        }
+    }
+
+    #[test]
+    fn prefilled_values_fit_their_inputs() {
+        let form = FormSettings {
+            content_max_length: 4500,
+            ..FormSettings::default()
+        };
+
+        let (first, continued) = split_content(&form, &"x".repeat(6000));
+        assert_eq!(first.chars().count(), 4000);
+        assert_eq!(continued.chars().count(), 500);
+
+        let form = FormSettings::default();
+        let (first, continued) = split_content(&form, &"é".repeat(3000));
+        assert_eq!(first.chars().count(), 2000);
+        assert!(continued.is_empty());
+
+        assert_eq!(capped("signature", 4), "sign");
+    }
+
+    #[test]
+    fn parse_duration_adds_up_units() {
+        assert_eq!(parse_duration("30m"), chrono::Duration::try_minutes(30));
+        assert_eq!(parse_duration("1d12h"), chrono::Duration::try_hours(36));
+        assert_eq!(parse_duration(" 2W "), chrono::Duration::try_days(14));
+    }
+
+    #[test]
+    fn parse_duration_rejects_invalid_input() {
+        for input in ["", "12", "d", "5x", "0m", "1h30"] {
+            assert_eq!(parse_duration(input), None, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn parse_duration_rejects_overflowing_totals() {
+        assert_eq!(parse_duration("99999999999999999999s"), None);
+        assert_eq!(parse_duration("15000000000000w"), None);
+        assert_eq!(parse_duration("9000000000000000s9000000000000000s"), None);
+    }
+
+    #[test]
+    fn split_text_prefers_paragraph_breaks() {
+        assert_eq!(
+            split_text("aaaa bbbb\n\ncccc dddd", 15),
+            ["aaaa bbbb", "cccc dddd"]
+        );
+    }
+
+    #[test]
+    fn split_text_falls_back_to_line_breaks_then_spaces() {
+        assert_eq!(
+            split_text("aaaa\nbbbb cccc dddd", 12),
+            ["aaaa", "bbbb cccc", "dddd"]
+        );
+        assert_eq!(
+            split_text("one two three four", 10),
+            ["one two", "three four"]
+        );
+    }
+
+    #[test]
+    fn split_text_breaks_exactly_at_max_chars() {
+        assert_eq!(split_text("abcde fghij", 5), ["abcde", "fghij"]);
+        assert_eq!(split_text("abcde\n\nfghij", 5), ["abcde", "fghij"]);
+        assert_eq!(split_text("abcde", 5), ["abcde"]);
+    }
+
+    #[test]
+    fn split_text_cuts_words_longer_than_max_chars() {
+        assert_eq!(split_text("abcdefghij", 4), ["abcd", "efgh", "ij"]);
+        assert_eq!(split_text("ab abcdefghij", 4), ["ab", "abcd", "efgh", "ij"]);
+    }
+
+    #[test]
+    fn split_text_counts_characters_not_bytes() {
+        assert_eq!(split_text("ééééé ééééé", 5), ["ééééé", "ééééé"]);
+        assert_eq!(
+            split_text("日本語日本語日本", 3),
+            ["日本語", "日本語", "日本"]
+        );
+        assert_eq!(split_text("🙂🙂 🙂🙂🙂", 3), ["🙂🙂", "🙂🙂🙂"]);
+    }
+
+    #[test]
+    fn split_text_drops_surrounding_whitespace() {
+        assert_eq!(split_text("  \n hello world \n\n", 20), ["hello world"]);
+        assert_eq!(split_text("hello   \n\n   world", 6), ["hello", "world"]);
+        assert!(split_text(" \n\n ", 5).is_empty());
+        assert!(split_text("", 5).is_empty());
+    }
+
+    #[test]
+    fn split_text_is_deterministic() {
+        let text = "Lorem ipsum dolor sit amet,\nconsectetur adipiscing elit.\n\nSed do eiusmod \
+                    tempor incididunt ut labore et dolore magna aliqua.";
+
+        let parts = split_text(text, 20);
+        assert_eq!(parts, split_text(text, 20));
+        assert!(parts.iter().all(|part| part.chars().count() <= 20));
+        assert_eq!(
+            parts.join(" ").split_whitespace().collect::<Vec<_>>(),
+            text.split_whitespace().collect::<Vec<_>>()
+        );
+    }
+}